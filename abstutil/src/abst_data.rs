@@ -93,7 +93,7 @@ impl DataPacks {
                     input: BTreeSet::new(),
                 };
                 cfg.runtime.insert("seattle".to_string());
-                crate::write_json(path, &cfg);
+                crate::write_json_with(path, &cfg, crate::JsonFormat::Pretty);
                 cfg
             }
         }
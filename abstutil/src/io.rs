@@ -1,22 +1,674 @@
-use std::collections::BTreeMap;
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::SystemTime;
 
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
-use crate::{basename, list_dir, maybe_read_binary, parent_path, slurp_file, Timer};
+use crate::{
+    basename, file_exists, list_dir, normalize_path, parent_path, try_read_binary,
+    try_read_compressed_binary, try_slurp_file, write_json, Parallelism, Timer,
+};
 
-pub fn maybe_read_json<T: DeserializeOwned>(path: String, timer: &mut Timer) -> Result<T, String> {
+/// Why reading and deserializing a file failed. The `maybe_read_*` functions flatten this into a
+/// string; use the `try_read_*` variants to distinguish a missing file from bad data.
+#[derive(Debug)]
+pub enum ReadError {
+    NotFound,
+    Io(std::io::Error),
+    Deserialize(String),
+    /// The file's md5sum didn't match its `.md5` sidecar.
+    ChecksumMismatch {
+        expected: String,
+        actual: String,
+    },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::NotFound => write!(f, "file not found"),
+            ReadError::Io(err) => write!(f, "{}", err),
+            ReadError::Deserialize(err) => write!(f, "couldn't deserialize: {}", err),
+            ReadError::ChecksumMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "checksum mismatch: expected {}, got {}",
+                    expected, actual
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+impl From<std::io::Error> for ReadError {
+    fn from(err: std::io::Error) -> ReadError {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            ReadError::NotFound
+        } else {
+            ReadError::Io(err)
+        }
+    }
+}
+
+impl From<ReadError> for std::io::Error {
+    fn from(err: ReadError) -> std::io::Error {
+        match err {
+            ReadError::NotFound => std::io::Error::from(std::io::ErrorKind::NotFound),
+            ReadError::Io(err) => err,
+            ReadError::Deserialize(err) => {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+            }
+            err @ ReadError::ChecksumMismatch { .. } => {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+            }
+        }
+    }
+}
+
+/// What a file looked like at some point, to notice somebody else changing it before
+/// `write_binary_if_not_newer` saves over it. Some filesystems only keep modification times to the
+/// second or two, so two writes in the same tick can share a time; the size and contents catch
+/// that.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileVersion {
+    pub mtime: SystemTime,
+    pub size: u64,
+    pub md5: String,
+}
+
+/// Why `write_binary_if_not_newer` didn't write anything.
+#[derive(Debug)]
+pub enum WriteError {
+    /// Someone else changed the file since the caller read it. `on_disk` is None if it was
+    /// deleted.
+    Conflict {
+        expected: Option<FileVersion>,
+        on_disk: Option<FileVersion>,
+    },
+    Failed(String),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::Conflict { on_disk: None, .. } => {
+                write!(f, "the file was deleted after it was loaded")
+            }
+            WriteError::Conflict { expected: None, .. } => {
+                write!(f, "someone else created the file in the meantime")
+            }
+            WriteError::Conflict { .. } => write!(f, "the file was modified after it was loaded"),
+            WriteError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// Caps how many bytes bincode may claim while decoding a file, so a corrupt or malicious length
+/// prefix fails to load instead of attempting a huge allocation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryLimit {
+    /// Decoding a valid file never needs more bytes than the file has, so this is the default.
+    FileSize,
+    /// A multiple of the file's size, for formats that expand while decoding.
+    FileSizeTimes(u64),
+    Bytes(u64),
+}
+
+impl Default for BinaryLimit {
+    fn default() -> BinaryLimit {
+        BinaryLimit::FileSize
+    }
+}
+
+impl BinaryLimit {
+    pub(crate) fn bytes(self, file_size: u64) -> u64 {
+        match self {
+            BinaryLimit::FileSize => file_size,
+            BinaryLimit::FileSizeTimes(n) => file_size.saturating_mul(n),
+            BinaryLimit::Bytes(n) => n,
+        }
+    }
+}
+
+/// How the body of a binary file is encoded. The header records this, so reading always works
+/// out the right one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryFormat {
+    /// Compact and fast, but not self-describing: any change to a type breaks old files.
+    Bincode,
+    /// Slower and bigger, but structs are stored as maps with field names. Files still load after
+    /// fields are added (with `#[serde(default)]`) or removed.
+    MessagePack,
+}
+
+impl Default for BinaryFormat {
+    fn default() -> BinaryFormat {
+        BinaryFormat::Bincode
+    }
+}
+
+impl BinaryFormat {
+    /// Serializes an object into a writer, without any header.
+    pub(crate) fn encode<W: Write, T: Serialize>(
+        self,
+        mut writer: W,
+        obj: &T,
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            BinaryFormat::Bincode => bincode::serialize_into(writer, obj).map_err(|x| x.into()),
+            BinaryFormat::MessagePack => {
+                rmp_serde::encode::write_named(&mut writer, obj).map_err(|x| x.into())
+            }
+        }
+    }
+}
+
+/// Maps logical names to the blobs returned by `write_binary_cas`. Many names can share one blob.
+/// Save this with `write_json` next to the blobs, and pass `live_hashes` to `gc_cas` once nothing
+/// refers to old blobs anymore.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CasManifest {
+    pub entries: BTreeMap<String, String>,
+}
+
+impl CasManifest {
+    pub fn live_hashes(&self) -> BTreeSet<String> {
+        self.entries.values().cloned().collect()
+    }
+}
+
+/// Where `write_binary_cas` puts a blob. Read it with `read_binary`.
+pub fn cas_path(dir: &str, hash: &str) -> String {
+    format!("{}/{}.bin", dir.trim_end_matches('/'), hash)
+}
+
+/// The serialized bytes of a content-addressed blob, and their hex md5sum. The header is part of
+/// what's hashed, so the same value in two different types gets two blobs.
+pub(crate) fn encode_cas_blob<T: Serialize>(obj: &T) -> Result<(String, Vec<u8>), Box<dyn Error>> {
+    let format = BinaryFormat::default();
+    let mut bytes = binary_header::<T>(format);
+    format.encode(&mut bytes, obj)?;
+    Ok((format!("{:x}", md5::compute(&bytes)), bytes))
+}
+
+// Names from write_binary_cas; other files living alongside the blobs are left alone
+pub(crate) fn is_cas_blob(path: &str) -> bool {
+    path.ends_with(".bin") && {
+        let name = basename(path);
+        name.len() == 32 && name.chars().all(|c| c.is_ascii_hexdigit())
+    }
+}
+
+/// How `write_binary_compressed` shrinks a file. Reading works out the algorithm from the first
+/// few bytes of the file, so only writers need to choose.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    /// From 0 (stored as-is) to 9 (smallest and slowest)
+    Gzip(u32),
+    /// From 1 to 21 (smallest and slowest). Only available with the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl Default for Compression {
+    /// gzip's own default, balancing speed and size
+    fn default() -> Compression {
+        Compression::Gzip(6)
+    }
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+impl Compression {
+    /// The extension that files compressed this way need, after `.bin`
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip(_) => ".gz",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(_) => ".zst",
+        }
+    }
+
+    /// Compresses everything `body` writes, then hands back the underlying writer.
+    pub(crate) fn compress<W: Write>(
+        self,
+        writer: W,
+        body: impl FnOnce(&mut dyn Write) -> Result<(), Box<dyn Error>>,
+    ) -> Result<W, Box<dyn Error>> {
+        match self {
+            Compression::Gzip(level) => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(writer, flate2::Compression::new(level.min(9)));
+                body(&mut encoder)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => {
+                let mut encoder = zstd::stream::write::Encoder::new(writer, level)?;
+                body(&mut encoder)?;
+                Ok(encoder.finish()?)
+            }
+        }
+    }
+}
+
+/// Undoes `Compression::compress`, detecting the algorithm from the magic bytes. Everything is
+/// decompressed up-front, so that `BinaryLimit` can be applied to the real size of the data.
+pub(crate) fn decompress<R: Read>(name: &str, mut reader: R) -> Result<Vec<u8>, ReadError> {
+    let mut magic = [0; 4];
+    if let Err(err) = reader.read_exact(&mut magic) {
+        return Err(if err.kind() == std::io::ErrorKind::UnexpectedEof {
+            ReadError::Deserialize(format!("{} is too short to be compressed", name))
+        } else {
+            err.into()
+        });
+    }
+    let reader = std::io::Cursor::new(magic).chain(reader);
+
+    let mut decoder: Box<dyn Read + '_> = if magic.starts_with(GZIP_MAGIC) {
+        Box::new(flate2::read::GzDecoder::new(reader))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        zstd_decoder(name, reader)?
+    } else {
+        return Err(ReadError::Deserialize(format!(
+            "{} isn't gzip or zstd compressed",
+            name
+        )));
+    };
+    let mut bytes = Vec::new();
+    decoder.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decoder<'a, R: Read + 'a>(_: &str, reader: R) -> Result<Box<dyn Read + 'a>, ReadError> {
+    Ok(Box::new(zstd::stream::read::Decoder::new(reader)?))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decoder<'a, R: Read + 'a>(name: &str, _: R) -> Result<Box<dyn Read + 'a>, ReadError> {
+    Err(ReadError::Deserialize(format!(
+        "{} uses zstd, but abstutil was built without the zstd feature",
+        name
+    )))
+}
+
+pub(crate) fn is_compressed_binary(path: &str) -> bool {
+    path.ends_with(".bin.gz") || path.ends_with(".bin.zst")
+}
+
+/// Decodes the body of a binary file. For bincode, claiming more than `limit` bytes is an error;
+/// MessagePack doesn't get this protection, so only use it for trusted files.
+pub(crate) fn deserialize_limited<T: DeserializeOwned, R: Read>(
+    name: &str,
+    format: BinaryFormat,
+    reader: R,
+    limit: u64,
+) -> Result<T, ReadError> {
+    use bincode::Options;
+
+    if format == BinaryFormat::MessagePack {
+        return rmp_serde::decode::from_read(reader)
+            .map_err(|err| ReadError::Deserialize(err.to_string()));
+    }
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit)
+        .deserialize_from(reader)
+        .map_err(|err| match *err {
+            bincode::ErrorKind::Io(err) => ReadError::Io(err),
+            bincode::ErrorKind::SizeLimit => ReadError::Deserialize(format!(
+                "{} claims to need more than the limit of {} bytes; it's corrupt or malicious",
+                name, limit
+            )),
+            err => ReadError::Deserialize(err.to_string()),
+        })
+}
+
+// Every .bin written by write_binary starts with this, followed by one byte of flags. The low bits
+// are the byte order the rest of the file was encoded with. If HAS_TYPE_TAG is set, next comes a
+// u16 length and the name of the type that was written. MESSAGEPACK means the rest is MessagePack
+// instead of bincode.
+const BINARY_MAGIC: &[u8; 4] = b"ABST";
+const LITTLE_ENDIAN: u8 = 0;
+const BIG_ENDIAN: u8 = 1;
+const HAS_TYPE_TAG: u8 = 0x80;
+const MESSAGEPACK: u8 = 0x40;
+// bincode's default config, which is what this crate always uses
+const OUR_ENDIANNESS: u8 = LITTLE_ENDIAN;
+
+pub(crate) fn binary_header<T>(format: BinaryFormat) -> Vec<u8> {
+    let tag = type_tag::<T>();
+    let mut header = BINARY_MAGIC.to_vec();
+    let mut flags = OUR_ENDIANNESS | HAS_TYPE_TAG;
+    if format == BinaryFormat::MessagePack {
+        flags |= MESSAGEPACK;
+    }
+    header.push(flags);
+    header.extend_from_slice(&(tag.len() as u16).to_le_bytes());
+    header.extend_from_slice(tag.as_bytes());
+    header
+}
+
+/// What's left of a binary file after `strip_binary_header`. For headerless files, the bytes read
+/// while looking for a header are put back in front.
+pub(crate) type BinaryBody<R> = std::io::Chain<std::io::Cursor<Vec<u8>>, R>;
+
+/// Checks and consumes the header written by `write_binary`, returning the format of the rest, how
+/// many bytes the header took up, and a reader over the rest. Fails if the file was written from a
+/// different type than `T`. Files without the header still load, with a warning.
+// TODO Stop accepting headerless files after the next release.
+pub(crate) fn strip_binary_header<T, R: Read>(
+    name: &str,
+    mut reader: R,
+) -> Result<(BinaryFormat, u64, BinaryBody<R>), ReadError> {
+    let describe = |x| match x {
+        LITTLE_ENDIAN => "little-endian",
+        BIG_ENDIAN => "big-endian",
+        _ => "an unknown",
+    };
+
+    let mut header = Vec::new();
+    let mut format = BinaryFormat::Bincode;
+    let mut header_len = 0;
+    (&mut reader).take(5).read_to_end(&mut header)?;
+    if header.len() == 5 && &header[..4] == BINARY_MAGIC {
+        if header[4] & MESSAGEPACK != 0 {
+            format = BinaryFormat::MessagePack;
+        }
+        let endianness = header[4] & !(HAS_TYPE_TAG | MESSAGEPACK);
+        if endianness != OUR_ENDIANNESS {
+            return Err(ReadError::Deserialize(format!(
+                "{} was written with {} config, reading with {}",
+                name,
+                describe(endianness),
+                describe(OUR_ENDIANNESS)
+            )));
+        }
+
+        header_len = 5;
+        let expected = type_tag::<T>();
+        if header[4] & HAS_TYPE_TAG != 0 {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            let mut tag = vec![0; u16::from_le_bytes(len) as usize];
+            reader.read_exact(&mut tag)?;
+            header_len += 2 + tag.len() as u64;
+            let actual = String::from_utf8_lossy(&tag);
+            // bincode will happily decode bytes as the wrong type, so this is the only check
+            if actual != expected {
+                return Err(ReadError::Deserialize(format!(
+                    "{} has the wrong type: expected {}, file contains {}",
+                    name, expected, actual
+                )));
+            }
+        } else {
+            warn!(
+                "{} has no type tag, so it can't be checked that it contains {}. Regenerate it.",
+                name, expected
+            );
+        }
+        header.clear();
+    } else {
+        warn!(
+            "{} has no header; assuming it's {}. Regenerate it, since headerless files won't \
+             load in the future.",
+            name,
+            describe(OUR_ENDIANNESS)
+        );
+    }
+    let body = std::io::Cursor::new(header).chain(reader);
+    Ok((format, header_len, body))
+}
+
+/// The name of `T` without module paths, like `Vec<ExtraShape>`, so moving a type to another
+/// module doesn't invalidate files. References serialize the same as what they point to, so
+/// they're dropped too.
+fn type_tag<T>() -> String {
+    strip_module_paths(std::any::type_name::<T>())
+        .replace("&mut ", "")
+        .replace('&', "")
+}
+
+fn strip_module_paths(name: &str) -> String {
+    let mut result = String::new();
+    let mut path = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+        } else {
+            result.push_str(path.rsplit("::").next().unwrap());
+            path.clear();
+            result.push(c);
+        }
+    }
+    result.push_str(path.rsplit("::").next().unwrap());
+    result
+}
+
+/// Passes bytes through to some consumer (like bincode), computing their md5sum along the way.
+pub struct HashingReader<R> {
+    inner: R,
+    context: md5::Context,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R) -> HashingReader<R> {
+        HashingReader {
+            inner,
+            context: md5::Context::new(),
+        }
+    }
+
+    /// Consumes anything the reader didn't get to, so the digest covers the entire input, then
+    /// returns the hex md5sum.
+    pub fn finish(mut self) -> std::io::Result<String> {
+        std::io::copy(&mut self, &mut std::io::sink())?;
+        Ok(format!("{:x}", self.context.compute()))
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.context.consume(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Compares a digest against the `<path>.md5` sidecar file.
+pub(crate) fn check_md5_sidecar(path: &str, actual: String) -> Result<(), ReadError> {
+    let raw = try_slurp_file(&format!("{}.md5", path))?;
+    let expected = String::from_utf8_lossy(&raw).trim().to_string();
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(ReadError::ChecksumMismatch { expected, actual })
+    }
+}
+
+/// The result of checking one file with `scan_integrity`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntegrityStatus {
+    Ok,
+    Corrupt(String),
+    /// A `.json` file holding binary data, or the other way around.
+    WrongExtension,
+}
+
+/// Checks a `.bin` or `.json` file without knowing what type it holds. JSON files must parse.
+/// Bincode isn't self-describing, so binary files only get their header checked, plus their
+/// checksum if there's a `.md5` sidecar.
+pub(crate) fn check_integrity(path: &str) -> IntegrityStatus {
+    let bytes = match try_slurp_file(path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            return IntegrityStatus::Corrupt(err.to_string());
+        }
+    };
+    let status = check_format(path, &bytes);
+    if status == IntegrityStatus::Ok && file_exists(format!("{}.md5", path)) {
+        if let Err(err) = check_md5_sidecar(path, format!("{:x}", md5::compute(&bytes))) {
+            return IntegrityStatus::Corrupt(err.to_string());
+        }
+    }
+    status
+}
+
+/// Like `check_integrity`, but ignoring any `.md5` sidecar.
+pub(crate) fn check_format(path: &str, bytes: &[u8]) -> IntegrityStatus {
+    let looks_binary = bytes.starts_with(BINARY_MAGIC);
+    let looks_json = bytes
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .map(|b| *b == b'{' || *b == b'[')
+        .unwrap_or(false);
+
+    if path.ends_with(".json") {
+        if looks_binary {
+            return IntegrityStatus::WrongExtension;
+        }
+        return match serde_json::from_slice::<serde_json::Value>(bytes) {
+            Ok(_) => IntegrityStatus::Ok,
+            Err(err) => IntegrityStatus::Corrupt(err.to_string()),
+        };
+    }
+
+    if !looks_binary && looks_json && serde_json::from_slice::<serde_json::Value>(bytes).is_ok() {
+        return IntegrityStatus::WrongExtension;
+    }
+    if looks_binary {
+        if bytes.len() < 5 {
+            return IntegrityStatus::Corrupt("truncated header".to_string());
+        }
+        let flags = bytes[4];
+        if flags & !(HAS_TYPE_TAG | MESSAGEPACK) != OUR_ENDIANNESS {
+            return IntegrityStatus::Corrupt(format!("unknown byte order {}", flags));
+        }
+        if flags & HAS_TYPE_TAG != 0 {
+            let tag_len = bytes
+                .get(5..7)
+                .map(|len| u16::from_le_bytes([len[0], len[1]]) as usize);
+            match tag_len {
+                Some(len) if bytes.len() >= 7 + len => {}
+                _ => {
+                    return IntegrityStatus::Corrupt("truncated type tag".to_string());
+                }
+            }
+        }
+    }
+    IntegrityStatus::Ok
+}
+
+/// One change made by `repair_directory`, or in a dry run, one change it would make.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RepairAction {
+    /// The contents didn't match the extension. The `.md5` sidecar, if any, moves too.
+    Renamed { from: String, to: String },
+    /// The `.md5` sidecar was older than the file, or missing and checksums were asked for, so it
+    /// was written.
+    RegeneratedChecksum(String),
+    /// Nothing was done about this file.
+    Unrepairable { path: String, problem: String },
+}
+
+pub fn try_read_json<T: DeserializeOwned>(path: String, timer: &mut Timer) -> Result<T, ReadError> {
     if !path.ends_with(".json") && !path.ends_with(".geojson") {
         panic!("read_json needs {} to end with .json or .geojson", path);
     }
 
     timer.start(format!("parse {}", path));
     // TODO timer.read_file isn't working here. And we need to call stop() if there's no file.
-    let result: Result<T, String> =
-        slurp_file(&path).and_then(|raw| serde_json::from_slice(&raw).map_err(|x| x.to_string()));
+    let result: Result<T, ReadError> = try_slurp_file(&path).and_then(|raw| {
+        serde_json::from_slice(&raw).map_err(|x| ReadError::Deserialize(x.to_string()))
+    });
     timer.stop(format!("parse {}", path));
     result
 }
 
+pub fn maybe_read_json<T: DeserializeOwned>(path: String, timer: &mut Timer) -> Result<T, String> {
+    try_read_json(path, timer).map_err(|err| err.to_string())
+}
+
+/// Like `try_read_json`, but any fields missing from the file take their value from
+/// `T::default()`. This is useful for reading old saves after a struct gains a field, when nobody
+/// remembered `#[serde(default)]`. Nested objects are filled in recursively; lists and other
+/// values are replaced wholesale.
+///
+/// This only works for JSON. Bincode isn't self-describing, so there's no way to tell which fields
+/// are missing from a binary file.
+pub fn read_json_lenient<T: Default + Serialize + DeserializeOwned>(
+    path: String,
+    timer: &mut Timer,
+) -> Result<T, ReadError> {
+    let raw: serde_json::Value = try_read_json(path, timer)?;
+    let mut value =
+        serde_json::to_value(T::default()).map_err(|x| ReadError::Deserialize(x.to_string()))?;
+    merge_json(&mut value, raw);
+    serde_json::from_value(value).map_err(|x| ReadError::Deserialize(x.to_string()))
+}
+
+fn merge_json(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => {
+            *base = overlay;
+        }
+    }
+}
+
+pub fn maybe_read_binary<T: DeserializeOwned>(
+    path: String,
+    timer: &mut Timer,
+) -> Result<T, String> {
+    try_read_binary(path, timer).map_err(|err| err.to_string())
+}
+
+/// Like `try_read_binary`, but a missing file isn't an error; it's `Ok(None)`. Use this for
+/// optional files, instead of checking `file_exists` first and racing with whoever removes it.
+pub fn read_binary_optional<T: DeserializeOwned>(
+    path: String,
+    timer: &mut Timer,
+) -> Result<Option<T>, ReadError> {
+    optional(try_read_binary(path, timer))
+}
+
+/// Like `try_read_json`, but a missing file is `Ok(None)`. See `read_binary_optional`.
+pub fn read_json_optional<T: DeserializeOwned>(
+    path: String,
+    timer: &mut Timer,
+) -> Result<Option<T>, ReadError> {
+    optional(try_read_json(path, timer))
+}
+
+fn optional<T>(result: Result<T, ReadError>) -> Result<Option<T>, ReadError> {
+    match result {
+        Ok(obj) => Ok(Some(obj)),
+        Err(ReadError::NotFound) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
 pub fn read_json<T: DeserializeOwned>(path: String, timer: &mut Timer) -> T {
     match maybe_read_json(path.clone(), timer) {
         Ok(obj) => obj,
@@ -31,9 +683,58 @@ pub fn read_binary<T: DeserializeOwned>(path: String, timer: &mut Timer) -> T {
     }
 }
 
-/// May be a JSON or binary file
+lazy_static::lazy_static! {
+    // Keyed by normalized path. Only weak references, so the data is freed once every user drops it.
+    static ref SHARED_BINARY: Mutex<HashMap<String, Weak<dyn Any + Send + Sync>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Like `try_read_binary`, but for large read-only data shared between many users. While any
+/// `Arc` from a previous call for the same path (and type) is still alive, this hands back the
+/// same one instead of reading the file again.
+pub fn read_binary_arc<T: 'static + DeserializeOwned + Send + Sync>(
+    path: String,
+    timer: &mut Timer,
+) -> Result<Arc<T>, ReadError> {
+    let key = normalize_path(&path);
+    let shared = SHARED_BINARY
+        .lock()
+        .unwrap()
+        .get(&key)
+        .and_then(|weak| weak.upgrade());
+    if let Some(Ok(obj)) = shared.map(|x| x.downcast::<T>()) {
+        timer.note(format!("Reusing {}, already in memory", path));
+        return Ok(obj);
+    }
+
+    let obj: Arc<T> = Arc::new(try_read_binary(path.clone(), timer)?);
+    let shared: Arc<dyn Any + Send + Sync> = obj.clone();
+    let mut cache = SHARED_BINARY.lock().unwrap();
+    cache.retain(|_, weak| weak.strong_count() > 0);
+    cache.insert(key, Arc::downgrade(&shared));
+    Ok(obj)
+}
+
+/// May be a JSON or binary file, possibly compressed
+pub fn try_read_object<T: DeserializeOwned>(
+    path: String,
+    timer: &mut Timer,
+) -> Result<T, ReadError> {
+    if is_compressed_binary(&path) {
+        try_read_compressed_binary(path, BinaryLimit::default(), timer)
+    } else if path.ends_with(".bin") {
+        try_read_binary(path, timer)
+    } else {
+        try_read_json(path, timer)
+    }
+}
+
+/// May be a JSON or binary file, possibly compressed
 pub fn read_object<T: DeserializeOwned>(path: String, timer: &mut Timer) -> Result<T, String> {
-    if path.ends_with(".bin") {
+    if is_compressed_binary(&path) {
+        try_read_compressed_binary(path, BinaryLimit::default(), timer)
+            .map_err(|err| err.to_string())
+    } else if path.ends_with(".bin") {
         maybe_read_binary(path, timer)
     } else {
         maybe_read_json(path, timer)
@@ -48,6 +749,87 @@ pub fn must_read_object<T: DeserializeOwned>(path: String, timer: &mut Timer) ->
     }
 }
 
+/// For golden tests: loads two JSON or binary files and panics if they differ, describing the first
+/// difference found. Much more helpful than comparing raw bytes.
+pub fn assert_saves_eq<T: DeserializeOwned + PartialEq + fmt::Debug>(
+    actual_path: String,
+    golden_path: String,
+) {
+    let mut timer = Timer::throwaway();
+    let actual: T = must_read_object(actual_path.clone(), &mut timer);
+    let golden: T = must_read_object(golden_path.clone(), &mut timer);
+    if actual == golden {
+        return;
+    }
+
+    let actual_debug = format!("{:#?}", actual);
+    let golden_debug = format!("{:#?}", golden);
+    let actual_lines: Vec<&str> = actual_debug.lines().collect();
+    let golden_lines: Vec<&str> = golden_debug.lines().collect();
+    let idx = actual_lines
+        .iter()
+        .zip(golden_lines.iter())
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| actual_lines.len().min(golden_lines.len()));
+    // If one is a prefix of the other, describe the path using the longer one
+    let lines = if idx < golden_lines.len() {
+        &golden_lines
+    } else {
+        &actual_lines
+    };
+    panic!(
+        "{} doesn't match {}. First difference at {}\n  expected: {}\n  actual:   {}",
+        actual_path,
+        golden_path,
+        debug_field_path(lines, idx),
+        golden_lines
+            .get(idx)
+            .map(|x| x.trim())
+            .unwrap_or("<nothing>"),
+        actual_lines
+            .get(idx)
+            .map(|x| x.trim())
+            .unwrap_or("<nothing>"),
+    );
+}
+
+// Pretty-printed Debug output indents nested fields, so walk upwards through the enclosing lines
+// to describe where line idx is.
+fn debug_field_path(lines: &[&str], idx: usize) -> String {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut path = Vec::new();
+    if let Some(line) = lines.get(idx) {
+        let mut current = indent(line);
+        path.push(line.trim());
+        for line in lines[0..idx].iter().rev() {
+            if indent(line) < current {
+                current = indent(line);
+                path.push(line.trim());
+            }
+        }
+    }
+    path.reverse();
+    path.into_iter()
+        .map(|x| x.trim_end_matches(|c| c == '{' || c == '[' || c == '(' || c == ','))
+        .map(|x| x.trim())
+        .collect::<Vec<_>>()
+        .join(" > ")
+}
+
+/// Reads every row of a .csv file. The first row must be a header, matching the fields of `T`.
+pub fn read_csv<T: DeserializeOwned>(path: String) -> Result<Vec<T>, Box<dyn Error>> {
+    if !path.ends_with(".csv") {
+        panic!("read_csv needs {} to end with .csv", path);
+    }
+
+    let raw = try_slurp_file(&path)?;
+    let mut rows = Vec::new();
+    for rec in csv::Reader::from_reader(&raw[..]).deserialize() {
+        rows.push(rec?);
+    }
+    Ok(rows)
+}
+
 /// Keeps file extensions
 pub fn find_prev_file(orig: String) -> Option<String> {
     let mut files = list_dir(parent_path(&orig));
@@ -78,7 +860,233 @@ pub fn load_all_objects<T: DeserializeOwned>(dir: String) -> Vec<(String, T)> {
     tree.into_iter().collect()
 }
 
+/// Whatever `load_all_objects_or_raw` could salvage from a file that isn't the requested type.
+#[derive(Debug)]
+pub struct RawData {
+    pub error: ReadError,
+    /// None if the file couldn't be read at all, or the caller didn't ask to keep the contents
+    pub contents: Option<RawContents>,
+}
+
+#[derive(Debug)]
+pub enum RawContents {
+    /// Valid JSON, just not the expected shape
+    Json(serde_json::Value),
+    /// A binary file, or something that isn't JSON at all
+    Bytes(Vec<u8>),
+}
+
+/// Like `load_all_objects`, but for directories that mix a few kinds of objects. A file that
+/// doesn't deserialize as a `T` is returned as an `Err` under its name, instead of being logged
+/// and dropped. With `keep_contents`, the error also holds the file's contents, so the caller can
+/// inspect it or try another type.
+pub fn load_all_objects_or_raw<T: DeserializeOwned>(
+    dir: String,
+    keep_contents: bool,
+) -> Vec<(String, Result<T, RawData>)> {
+    let mut timer = Timer::new(format!("load_all_objects from {}", dir));
+    let mut tree: BTreeMap<String, Result<T, RawData>> = BTreeMap::new();
+    for path in list_dir(dir) {
+        let is_json = path.ends_with(".json") || path.ends_with(".geojson");
+        let result = if is_json || path.ends_with(".bin") || is_compressed_binary(&path) {
+            try_read_object(path.clone(), &mut timer)
+        } else {
+            Err(ReadError::Deserialize(format!(
+                "{} isn't a .bin or .json file",
+                path
+            )))
+        };
+        let result = result.map_err(|error| {
+            let contents = if keep_contents {
+                try_slurp_file(&path).ok().map(|bytes| {
+                    if is_json {
+                        match serde_json::from_slice(&bytes) {
+                            Ok(value) => RawContents::Json(value),
+                            Err(_) => RawContents::Bytes(bytes),
+                        }
+                    } else {
+                        RawContents::Bytes(bytes)
+                    }
+                })
+            } else {
+                None
+            };
+            RawData { error, contents }
+        });
+        tree.insert(basename(path), result);
+    }
+    tree.into_iter().collect()
+}
+
+/// Like `load_all_objects`, but restartable after a crash or cancellation. As each file loads, its
+/// name is added to a JSON manifest at `manifest_path`. To resume, pass in whatever the interrupted
+/// call already loaded; anything in both that and the manifest isn't read again. With `previous`
+/// as None, an old manifest is ignored and everything is loaded.
+pub fn load_all_objects_resumable<T: DeserializeOwned>(
+    dir: String,
+    manifest_path: String,
+    previous: Option<Vec<(String, T)>>,
+) -> Vec<(String, T)> {
+    let mut timer = Timer::new(format!("load_all_objects from {}", dir));
+    let mut tree: BTreeMap<String, T> = BTreeMap::new();
+    let mut manifest: BTreeSet<String> = BTreeSet::new();
+    if let Some(previous) = previous {
+        let done: BTreeSet<String> =
+            maybe_read_json(manifest_path.clone(), &mut timer).unwrap_or_default();
+        for (name, obj) in previous {
+            if done.contains(&name) {
+                manifest.insert(name.clone());
+                tree.insert(name, obj);
+            }
+        }
+    }
+    for path in list_dir(dir) {
+        let name = basename(path.clone());
+        if tree.contains_key(&name) {
+            continue;
+        }
+        match read_object(path.clone(), &mut timer) {
+            Ok(obj) => {
+                tree.insert(name.clone(), obj);
+                manifest.insert(name);
+                write_json(manifest_path.clone(), &manifest);
+            }
+            Err(err) => {
+                error!("Couldn't load {}: {}", path, err);
+            }
+        }
+    }
+    tree.into_iter().collect()
+}
+
+/// Like `load_all_objects`, but only loads the named files, trying `.bin` before `.json`. Results
+/// are in the same order as `names`, with repeats loaded once. Names without a file are skipped
+/// with a warning.
+pub fn load_objects<T: DeserializeOwned>(dir: String, names: &[String]) -> Vec<(String, T)> {
+    let mut timer = Timer::new(format!("load_objects from {}", dir));
+    let dir = dir.trim_end_matches('/');
+    let mut results = Vec::new();
+    let mut missing = Vec::new();
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(normalize_path(name)) {
+            continue;
+        }
+        let bin = format!("{}/{}.bin", dir, name);
+        let json = format!("{}/{}.json", dir, name);
+        let path = if file_exists(bin.clone()) {
+            bin
+        } else if file_exists(json.clone()) {
+            json
+        } else {
+            missing.push(name.clone());
+            continue;
+        };
+        match read_object(path.clone(), &mut timer) {
+            Ok(obj) => {
+                results.push((name.clone(), obj));
+            }
+            Err(err) => {
+                error!("Couldn't load {}: {}", path, err);
+            }
+        }
+    }
+    if !missing.is_empty() {
+        warn!("load_objects from {} didn't find {:?}", dir, missing);
+    }
+    results
+}
+
+/// Loads every file in a directory and calls `f` on each object, spread across all CPUs. Results
+/// are sorted by name, with the file extension removed, just like `load_all_objects`. A file that
+/// can't be loaded doesn't stop the others; its error is logged and returned under its name.
+pub fn par_map_files<T, R, F>(
+    dir: String,
+    timer: &mut Timer,
+    f: F,
+) -> Vec<(String, Result<R, ReadError>)>
+where
+    T: DeserializeOwned,
+    R: Send,
+    F: Fn(String, T) -> R + Sync,
+{
+    let f = &f;
+    let mut results = timer.parallelize(
+        &format!("map over {}", dir),
+        Parallelism::Fastest,
+        list_dir(dir.clone()),
+        move |path| {
+            let name = basename(&path);
+            // Workers can't share the caller's timer; it tracks progress as results come back
+            let result =
+                try_read_object(path, &mut Timer::throwaway()).map(|obj| f(name.clone(), obj));
+            (name, result)
+        },
+    );
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|(_, result)| result.is_err())
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !failed.is_empty() {
+        error!(
+            "par_map_files over {} couldn't load {} files: {:?}",
+            dir,
+            failed.len(),
+            failed
+        );
+    }
+    results
+}
+
 /// Just list all things from a directory, return sorted by name, with file extension removed.
 pub fn list_all_objects(dir: String) -> Vec<String> {
     list_dir(dir).into_iter().map(basename).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_json_fills_in_nested_objects() {
+        let mut base = serde_json::json!({
+            "query": "",
+            "camera": {"zoom": 1.0, "follow": true},
+            "layers": ["roads", "parcels"],
+        });
+        merge_json(
+            &mut base,
+            serde_json::json!({
+                "camera": {"zoom": 3.0},
+                "layers": ["roads"],
+                "theme": "dark",
+            }),
+        );
+        assert_eq!(
+            base,
+            serde_json::json!({
+                "query": "",
+                "camera": {"zoom": 3.0, "follow": true},
+                "layers": ["roads"],
+                "theme": "dark",
+            })
+        );
+    }
+
+    #[test]
+    fn type_tags_skip_module_paths() {
+        assert_eq!(
+            strip_module_paths("alloc::vec::Vec<kml::ExtraShape>"),
+            "Vec<ExtraShape>"
+        );
+        assert_eq!(
+            strip_module_paths("(usize, alloc::string::String)"),
+            "(usize, String)"
+        );
+        assert_eq!(type_tag::<Vec<String>>(), "Vec<String>");
+        assert_eq!(type_tag::<&Vec<&String>>(), "Vec<String>");
+    }
+}
@@ -1,29 +1,60 @@
 //! Normal file IO using the filesystem
 
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
 use std::error::Error;
 use std::fs::File;
-use std::io::{stdout, BufReader, BufWriter, Read, Write};
+use std::io::{stdout, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
 
 use instant::Instant;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 pub use crate::io::*;
 use crate::time::{clear_current_line, prettyprint_time};
-use crate::{elapsed_seconds, prettyprint_usize, to_json, Timer, PROGRESS_FREQUENCY_SECONDS};
+use crate::{
+    basename, elapsed_seconds, prettyprint_usize, JsonFormat, Timer, PROGRESS_FREQUENCY_SECONDS,
+};
 
 pub fn file_exists<I: Into<String>>(path: I) -> bool {
     Path::new(&path.into()).exists()
 }
 
-/// Returns full paths
+/// True if the file at `cache_path` is missing or older than any of the inputs it was derived
+/// from. Inputs that don't exist are ignored.
+pub fn is_cache_stale(cache_path: &str, inputs: &[String]) -> bool {
+    let cache_time = match std::fs::metadata(cache_path).and_then(|m| m.modified()) {
+        Ok(t) => t,
+        Err(_) => {
+            return true;
+        }
+    };
+    inputs.iter().any(|path| {
+        std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map(|t| t > cache_time)
+            .unwrap_or(false)
+    })
+}
+
+/// Returns full paths. Files whose names aren't valid UTF-8 are skipped with a warning.
 pub fn list_dir(path: String) -> Vec<String> {
     let mut files: Vec<String> = Vec::new();
     match std::fs::read_dir(&path) {
         Ok(iter) => {
             for entry in iter {
-                files.push(entry.unwrap().path().to_str().unwrap().to_string());
+                let entry_path = entry.unwrap().path();
+                match entry_path.to_str() {
+                    Some(p) => files.push(p.to_string()),
+                    None => warn!("Skipping {}, its name isn't UTF-8", entry_path.display()),
+                }
             }
         }
         Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {}
@@ -33,49 +64,278 @@ pub fn list_dir(path: String) -> Vec<String> {
     files
 }
 
+lazy_static::lazy_static! {
+    // Directory to its modification time and sorted object names, for list_objects_page
+    static ref OBJECT_NAMES: Mutex<HashMap<String, (SystemTime, Arc<Vec<String>>)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// One page of `list_all_objects`: up to `limit` names starting at `offset`, along with the total
+/// number of objects. For file pickers over huge directories, this remembers the sorted names and
+/// only lists the directory again after its modification time changes.
+pub fn list_objects_page(dir: String, offset: usize, limit: usize) -> (Vec<String>, usize) {
+    let names = cached_object_names(dir);
+    let page = names.iter().skip(offset).take(limit).cloned().collect();
+    (page, names.len())
+}
+
+fn cached_object_names(dir: String) -> Arc<Vec<String>> {
+    let mtime = file_mtime(&dir);
+    let mut cache = OBJECT_NAMES.lock().unwrap();
+    if let (Some(mtime), Some((cached_mtime, names))) = (mtime, cache.get(&dir)) {
+        if mtime == *cached_mtime {
+            return names.clone();
+        }
+    }
+
+    let names = Arc::new(list_all_objects(dir.clone()));
+    // Some filesystems only track modification time to the second. If the directory just changed,
+    // it might change again without the time moving, so don't trust it yet.
+    let settled = mtime
+        .and_then(|t| t.elapsed().ok())
+        .map(|age| age > Duration::from_secs(2))
+        .unwrap_or(false);
+    if settled {
+        cache.insert(dir, (mtime.unwrap(), names.clone()));
+    } else {
+        cache.remove(&dir);
+    }
+    names
+}
+
+/// Recursively checks every `.bin` and `.json` file in a directory, without knowing what types
+/// they hold. See `IntegrityStatus`.
+pub fn scan_integrity(dir: String) -> Vec<(String, IntegrityStatus)> {
+    let mut paths = Vec::new();
+    find_data_files(dir.clone(), &mut paths);
+
+    let mut timer = Timer::new(format!("scan integrity of {}", dir));
+    timer.start_iter("check files", paths.len());
+    paths
+        .into_iter()
+        .map(|path| {
+            timer.next();
+            let status = check_integrity(&path);
+            (path, status)
+        })
+        .collect()
+}
+
+/// Recursively fixes what it can in a directory of saves: files whose contents don't match their
+/// extension are renamed, and `.md5` sidecars older than their file are regenerated. Files without
+/// a sidecar only get one with `add_checksums`. Truncated and otherwise corrupt files aren't
+/// repaired, and neither are checksum mismatches; they're only reported, since there's no reliable
+/// way to salvage data of an unknown type. With `dry_run`, nothing is changed, but the same
+/// actions are returned.
+pub fn repair_directory(dir: String, dry_run: bool, add_checksums: bool) -> Vec<RepairAction> {
+    let mut paths = Vec::new();
+    find_data_files(dir.clone(), &mut paths);
+
+    let mut timer = Timer::new(format!("repair {}", dir));
+    timer.start_iter("check files", paths.len());
+    let mut actions = Vec::new();
+    for path in paths {
+        timer.next();
+        if let Err(err) = repair_file(&path, dry_run, add_checksums, &mut actions) {
+            actions.push(RepairAction::Unrepairable {
+                path,
+                problem: err.to_string(),
+            });
+        }
+    }
+    actions
+}
+
+fn repair_file(
+    path: &str,
+    dry_run: bool,
+    add_checksums: bool,
+    actions: &mut Vec<RepairAction>,
+) -> Result<(), Box<dyn Error>> {
+    let bytes = try_slurp_file(path)?;
+    let sidecar = format!("{}.md5", path);
+    match check_format(path, &bytes) {
+        IntegrityStatus::Ok => {
+            let digest = format!("{:x}", md5::compute(&bytes));
+            let has_sidecar = file_exists(sidecar.clone());
+            // Unless asked, don't scatter new sidecars around
+            if (has_sidecar || add_checksums) && is_cache_stale(&sidecar, &[path.to_string()]) {
+                if !dry_run {
+                    std::fs::write(&sidecar, digest)?;
+                }
+                actions.push(RepairAction::RegeneratedChecksum(path.to_string()));
+            } else if has_sidecar {
+                // The sidecar is newer than the file, so the file changed unexpectedly
+                check_md5_sidecar(path, digest)?;
+            }
+        }
+        IntegrityStatus::WrongExtension => {
+            let to = if let Some(base) = path.strip_suffix(".json") {
+                format!("{}.bin", base)
+            } else {
+                format!("{}.json", path.trim_end_matches(".bin"))
+            };
+            if file_exists(to.clone()) {
+                return Err(format!("should be named {}, but that already exists", to).into());
+            }
+            if !dry_run {
+                std::fs::rename(path, &to)?;
+                if file_exists(sidecar.clone()) {
+                    std::fs::rename(&sidecar, format!("{}.md5", to))?;
+                }
+            }
+            actions.push(RepairAction::Renamed {
+                from: path.to_string(),
+                to,
+            });
+        }
+        IntegrityStatus::Corrupt(problem) => {
+            actions.push(RepairAction::Unrepairable {
+                path: path.to_string(),
+                problem,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn find_data_files(dir: String, results: &mut Vec<String>) {
+    for path in list_dir(dir) {
+        if Path::new(&path).is_dir() {
+            find_data_files(path, results);
+        } else if path.ends_with(".bin") || path.ends_with(".json") {
+            results.push(path);
+        }
+    }
+}
+
 pub fn slurp_file(path: &str) -> Result<Vec<u8>, String> {
-    inner_slurp_file(path).map_err(|err| err.to_string())
+    try_slurp_file(path).map_err(|err| err.to_string())
 }
-fn inner_slurp_file(path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+
+pub fn try_slurp_file(path: &str) -> Result<Vec<u8>, ReadError> {
     let mut file = File::open(path)?;
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
     Ok(buffer)
 }
 
-pub fn maybe_read_binary<T: DeserializeOwned>(
+pub fn try_read_binary<T: DeserializeOwned>(
+    path: String,
+    timer: &mut Timer,
+) -> Result<T, ReadError> {
+    try_read_binary_with_limit(path, BinaryLimit::default(), timer)
+}
+
+/// Like `try_read_binary`, but choosing how much bincode may allocate. Loosen this only for files
+/// that are trusted.
+pub fn try_read_binary_with_limit<T: DeserializeOwned>(
+    path: String,
+    limit: BinaryLimit,
+    timer: &mut Timer,
+) -> Result<T, ReadError> {
+    if !path.ends_with(".bin") {
+        panic!("read_binary needs {} to end with .bin", path);
+    }
+
+    read_timed(&path, timer, |timer| {
+        let limit = limit.bytes(std::fs::metadata(&path)?.len());
+        let (format, _, body) = strip_binary_header::<T, _>(&path, timer)?;
+        deserialize_limited(&path, format, body, limit)
+    })
+}
+
+/// Reads a file written by `write_binary_compressed`. The algorithm comes from the file's contents,
+/// not its name. `limit` applies to the decompressed size.
+pub fn try_read_compressed_binary<T: DeserializeOwned>(
+    path: String,
+    limit: BinaryLimit,
+    timer: &mut Timer,
+) -> Result<T, ReadError> {
+    if !is_compressed_binary(&path) {
+        panic!(
+            "read_compressed_binary needs {} to end with .bin.gz or .bin.zst",
+            path
+        );
+    }
+
+    read_timed(&path, timer, |timer| {
+        let bytes = decompress(&path, timer)?;
+        let limit = limit.bytes(bytes.len() as u64);
+        let (format, _, body) = strip_binary_header::<T, _>(&path, &bytes[..])?;
+        deserialize_limited(&path, format, body, limit)
+    })
+}
+
+/// Like `try_read_binary`, but also checks the file against its `<path>.md5` sidecar. The file is
+/// hashed while bincode reads it, so large files aren't read twice.
+pub fn read_binary_verified<T: DeserializeOwned>(
     path: String,
     timer: &mut Timer,
-) -> Result<T, String> {
+) -> Result<T, ReadError> {
     if !path.ends_with(".bin") {
         panic!("read_binary needs {} to end with .bin", path);
     }
 
-    timer.read_file(&path)?;
-    bincode::deserialize_from(timer).map_err(|x| x.to_string())
+    read_timed(&path, timer, |timer| {
+        let limit = BinaryLimit::default().bytes(std::fs::metadata(&path)?.len());
+        let mut reader = HashingReader::new(timer);
+        let (format, _, body) = strip_binary_header::<T, _>(&path, &mut reader)?;
+        let obj = deserialize_limited(&path, format, body, limit)?;
+        check_md5_sidecar(&path, reader.finish()?)?;
+        Ok(obj)
+    })
+}
+
+// Reads the file through the timer, taking it back off the timer's stack if `read` fails partway
+fn read_timed<T, F: FnOnce(&mut Timer) -> Result<T, ReadError>>(
+    path: &str,
+    timer: &mut Timer,
+    read: F,
+) -> Result<T, ReadError> {
+    timer.read_file(path)?;
+    let result = read(timer);
+    if result.is_err() {
+        timer.abandon_file(path);
+    }
+    result
 }
 
 // TODO Idea: Have a wrapper type DotJSON(...) and DotBin(...) to distinguish raw path strings
-fn maybe_write_json<T: Serialize>(path: &str, obj: &T) -> Result<(), Box<dyn Error>> {
+fn maybe_write_json<T: Serialize>(
+    path: &str,
+    obj: &T,
+    format: JsonFormat,
+) -> Result<(), Box<dyn Error>> {
     if !path.ends_with(".json") {
         panic!("write_json needs {} to end with .json", path);
     }
     std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())
         .expect("Creating parent dir failed");
 
-    let mut file = File::create(path)?;
-    file.write_all(to_json(obj).as_bytes())?;
+    let mut file = BufWriter::new(File::create(path)?);
+    format.write_to(&mut file, obj)?;
+    file.flush()?;
     Ok(())
 }
 
+/// Writes compact JSON. Use `write_json_with` for something people will read or diff.
 pub fn write_json<T: Serialize>(path: String, obj: &T) {
-    if let Err(err) = maybe_write_json(&path, obj) {
+    write_json_with(path, obj, JsonFormat::Compact);
+}
+
+pub fn write_json_with<T: Serialize>(path: String, obj: &T, format: JsonFormat) {
+    if let Err(err) = maybe_write_json(&path, obj, format) {
         panic!("Can't write_json({}): {}", path, err);
     }
-    println!("Wrote {}", path);
+    wrote_file(&path);
 }
 
-fn maybe_write_binary<T: Serialize>(path: &str, obj: &T) -> Result<(), Box<dyn Error>> {
+fn maybe_write_binary<T: Serialize>(
+    path: &str,
+    obj: &T,
+    format: BinaryFormat,
+) -> Result<(), Box<dyn Error>> {
     if !path.ends_with(".bin") {
         panic!("write_binary needs {} to end with .bin", path);
     }
@@ -83,15 +343,843 @@ fn maybe_write_binary<T: Serialize>(path: &str, obj: &T) -> Result<(), Box<dyn E
     std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())
         .expect("Creating parent dir failed");
 
-    let file = BufWriter::new(File::create(path)?);
-    bincode::serialize_into(file, obj).map_err(|x| x.into())
+    encode_binary(BufWriter::new(File::create(path)?), obj, format)
+}
+
+fn encode_binary<W: Write, T: Serialize>(
+    mut writer: W,
+    obj: &T,
+    format: BinaryFormat,
+) -> Result<(), Box<dyn Error>> {
+    writer.write_all(&binary_header::<T>(format))?;
+    format.encode(writer, obj)
 }
 
 pub fn write_binary<T: Serialize>(path: String, obj: &T) {
-    if let Err(err) = maybe_write_binary(&path, obj) {
+    write_binary_with(path, obj, BinaryFormat::default());
+}
+
+/// Like `write_binary`, but choosing the encoding. Reading detects it automatically.
+pub fn write_binary_with<T: Serialize>(path: String, obj: &T, format: BinaryFormat) {
+    if let Err(err) = maybe_write_binary(&path, obj, format) {
         panic!("Can't write_binary({}): {}", path, err);
     }
-    println!("Wrote {}", path);
+    wrote_file(&path);
+}
+
+/// Like `write_binary`, but compressed. The path has to end with `.bin.gz` for gzip or `.bin.zst`
+/// for zstd. `read_object` and `try_read_compressed_binary` detect the algorithm.
+///
+/// Picking a `Compression`, roughly:
+/// - bincode output for maps and simulations is very repetitive, so even the fastest levels shrink
+///   it a lot. Most of the savings come from the first few levels.
+/// - gzip levels 1-3 suit frequent writes, like autosaves. Past the default of 6, writing gets
+///   much slower for a few percent more.
+/// - zstd at level 3 is typically as small as gzip at 9, writes faster than gzip at 6, and reads
+///   several times faster than any gzip level. Levels past 19 are only worth it for archives
+///   that are written once and downloaded often.
+///
+/// Before relying on these, measure with real data: time a save and compare file sizes.
+pub fn write_binary_compressed<T: Serialize>(path: String, obj: &T, compression: Compression) {
+    if let Err(err) = maybe_write_binary_compressed(&path, obj, compression) {
+        panic!("Can't write_binary_compressed({}): {}", path, err);
+    }
+    wrote_file(&path);
+}
+
+fn maybe_write_binary_compressed<T: Serialize>(
+    path: &str,
+    obj: &T,
+    compression: Compression,
+) -> Result<(), Box<dyn Error>> {
+    let extension = format!(".bin{}", compression.extension());
+    if !path.ends_with(&extension) {
+        panic!(
+            "write_binary_compressed with {:?} needs {} to end with {}",
+            compression, path, extension
+        );
+    }
+    std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())
+        .expect("Creating parent dir failed");
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut file = compression.compress(file, |writer| {
+        encode_binary(writer, obj, BinaryFormat::default())
+    })?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Like `write_binary`, but the data is really on disk once this returns, surviving a crash or
+/// power loss right afterwards. The file is written to a temporary path, flushed and fsynced, and
+/// then renamed into place. fsync can take tens of milliseconds (or much longer on a busy spinning
+/// disk), so only use this for files that users would be upset to lose, like autosaves.
+pub fn write_binary_durable<T: Serialize>(path: String, obj: &T) {
+    if let Err(err) = maybe_write_binary_durable(&path, obj) {
+        panic!("Can't write_binary_durable({}): {}", path, err);
+    }
+    wrote_file(&path);
+}
+
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // (this thread's number, how many names it's handed out)
+    static TEMPFILE_COUNTER: Cell<(usize, usize)> =
+        Cell::new((NEXT_THREAD.fetch_add(1, Ordering::Relaxed), 0));
+    // Only set by seed_tempfile_names
+    static TEMPFILE_RNG: RefCell<Option<XorShiftRng>> = RefCell::new(None);
+}
+
+/// Where to write `final_path` before renaming it into place. The name includes the process ID,
+/// the calling thread, and a counter, so concurrent writers to the same target never share a
+/// temporary file; a short random suffix also avoids files left behind by a crashed process that
+/// had the same ID.
+pub fn tempfile_name(final_path: &str) -> String {
+    let (thread, count) = TEMPFILE_COUNTER.with(|c| {
+        let (thread, count) = c.get();
+        c.set((thread, count + 1));
+        (thread, count)
+    });
+    let suffix: u16 = TEMPFILE_RNG.with(|rng| match rng.borrow_mut().as_mut() {
+        Some(rng) => rng.gen(),
+        None => rand::random(),
+    });
+    format!(
+        "{}.tmp.{}.{}.{}.{:04x}",
+        final_path,
+        std::process::id(),
+        thread,
+        count,
+        suffix
+    )
+}
+
+/// Makes the names from `tempfile_name` on this thread reproducible, for tests. Resets the counter
+/// and draws the random suffix from `seed` from now on.
+pub fn seed_tempfile_names(seed: u64) {
+    TEMPFILE_COUNTER.with(|c| c.set((c.get().0, 0)));
+    TEMPFILE_RNG.with(|rng| *rng.borrow_mut() = Some(XorShiftRng::seed_from_u64(seed)));
+}
+
+fn maybe_write_binary_durable<T: Serialize>(path: &str, obj: &T) -> Result<(), Box<dyn Error>> {
+    if !path.ends_with(".bin") {
+        panic!("write_binary needs {} to end with .bin", path);
+    }
+    std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())
+        .expect("Creating parent dir failed");
+
+    let tmp_path = tempfile_name(path);
+    let result = write_new_file(&tmp_path, |file| {
+        encode_binary(&mut *file, obj, BinaryFormat::default())?;
+        file.flush()?;
+        file.get_ref().sync_all()?;
+        Ok(())
+    });
+    if let Err(err) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+    std::fs::rename(&tmp_path, path)?;
+    // The rename itself only survives a crash once the directory entry is synced too. Windows
+    // can't open directories like this.
+    #[cfg(unix)]
+    File::open(Path::new(path).parent().unwrap())?.sync_all()?;
+    Ok(())
+}
+
+/// Content-addressed storage: saves an object as `<dir>/<hash>.bin`, named by the md5sum of its
+/// serialized bytes, and returns the hash. If that blob is already there, nothing is written, so
+/// many saves sharing an identical piece only store it once. Record the hash in a `CasManifest`,
+/// and read it back with `read_binary(cas_path(dir, hash))`.
+pub fn write_binary_cas<T: Serialize>(dir: String, obj: &T) -> String {
+    match maybe_write_binary_cas(&dir, obj) {
+        Ok(hash) => hash,
+        Err(err) => panic!("Can't write_binary_cas({}): {}", dir, err),
+    }
+}
+
+fn maybe_write_binary_cas<T: Serialize>(dir: &str, obj: &T) -> Result<String, Box<dyn Error>> {
+    let (hash, bytes) = encode_cas_blob(obj)?;
+    let path = cas_path(dir, &hash);
+    if file_exists(path.clone()) {
+        return Ok(hash);
+    }
+    std::fs::create_dir_all(dir)?;
+    // Somebody else might be writing the same blob right now. Renaming a finished file into place
+    // means nobody sees half of one.
+    let tmp_path = tempfile_name(&path);
+    let result = write_new_file(&tmp_path, |file| {
+        file.write_all(&bytes)?;
+        Ok(())
+    });
+    if let Err(err) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+    std::fs::rename(&tmp_path, &path)?;
+    wrote_file(&path);
+    Ok(hash)
+}
+
+/// Deletes blobs written by `write_binary_cas` whose hash isn't in `live_hashes`, returning how
+/// many were removed. Other files in the directory are left alone. Don't run this while something
+/// might be saving; a blob that was just written but isn't in a manifest yet looks unreferenced.
+pub fn gc_cas(dir: String, live_hashes: &BTreeSet<String>) -> usize {
+    let mut removed = 0;
+    for path in list_dir(dir) {
+        if is_cas_blob(&path) && !live_hashes.contains(&basename(&path)) {
+            match std::fs::remove_file(&path) {
+                Ok(()) => {
+                    removed += 1;
+                }
+                Err(err) => {
+                    warn!("Couldn't remove {}: {}", path, err);
+                }
+            }
+        }
+    }
+    removed
+}
+
+/// When a file was last modified, or None if it doesn't exist.
+pub fn file_mtime(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The file's modification time, size, and md5sum, or None if it doesn't exist. Remember this when
+/// loading something, to pass to `write_binary_if_not_newer` later. This reads the whole file.
+pub fn file_version(path: &str) -> Option<FileVersion> {
+    let mut file = File::open(path).ok()?;
+    let metadata = file.metadata().ok()?;
+    let mut context = md5::Context::new();
+    std::io::copy(&mut file, &mut context).ok()?;
+    Some(FileVersion {
+        mtime: metadata.modified().ok()?,
+        size: metadata.len(),
+        md5: format!("{:x}", context.compute()),
+    })
+}
+
+/// Like `write_binary`, but refuses to clobber somebody else's changes. `expected` is the file's
+/// version when the caller read it, or None if there was no file yet; if the file on disk doesn't
+/// match that anymore, nothing is written and the caller can ask the user what to do. Returns the
+/// new version, for the next save.
+///
+/// The new version is written to a temporary file first, and the check happens right before
+/// renaming it in place, so the window for a conflicting write to sneak in is tiny -- but it's not
+/// zero. This doesn't replace real locking when many writers are expected. Somebody else writing
+/// exactly the same bytes isn't noticed, which is harmless. Since the file is hashed before and
+/// after saving, this is slow for big files.
+pub fn write_binary_if_not_newer<T: Serialize>(
+    path: String,
+    obj: &T,
+    expected: Option<FileVersion>,
+) -> Result<FileVersion, WriteError> {
+    if !path.ends_with(".bin") {
+        panic!("write_binary needs {} to end with .bin", path);
+    }
+    std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap())
+        .expect("Creating parent dir failed");
+
+    let tmp_path = tempfile_name(&path);
+    let result = write_new_file(&tmp_path, |file| {
+        encode_binary(&mut *file, obj, BinaryFormat::default())
+    });
+    if let Err(err) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(WriteError::Failed(err.to_string()));
+    }
+
+    let on_disk = file_version(&path);
+    if on_disk != expected {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(WriteError::Conflict { expected, on_disk });
+    }
+    std::fs::rename(&tmp_path, &path).map_err(|err| WriteError::Failed(err.to_string()))?;
+    wrote_file(&path);
+    file_version(&path).ok_or_else(|| WriteError::Failed(format!("{} vanished after saving", path)))
+}
+
+/// Saves something in the background while it keeps changing. Call `mark_dirty` after each change,
+/// with a closure producing what to save. At most one save happens per interval; changes made
+/// while waiting replace each other, so only the newest is written. The closure runs on the
+/// background thread, and saves use `write_binary_durable`.
+///
+/// Dropping this (or calling `finish`) writes any change not saved yet, waiting for it.
+pub struct Autosaver {
+    shared: Arc<(Mutex<AutosaveState>, Condvar)>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+type PendingSave = Box<dyn FnOnce(&str) -> Result<(), String> + Send>;
+
+#[derive(Default)]
+struct AutosaveState {
+    // The newest change, not written yet
+    pending: Option<PendingSave>,
+    last_attempt: Option<Instant>,
+    last_saved: Option<Instant>,
+    // From the most recent attempt
+    error: Option<String>,
+    shutdown: bool,
+}
+
+impl Autosaver {
+    pub fn new(path: String, interval: Duration) -> Autosaver {
+        if !path.ends_with(".bin") {
+            panic!("Autosaver needs {} to end with .bin", path);
+        }
+        let shared = Arc::new((Mutex::new(AutosaveState::default()), Condvar::new()));
+        let thread = {
+            let shared = shared.clone();
+            std::thread::spawn(move || autosave_loop(path, interval, shared))
+        };
+        Autosaver {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Something changed. `serialize` will be called later on the background thread, unless
+    /// another change replaces it first.
+    pub fn mark_dirty<T: Serialize, F: 'static + Send + FnOnce() -> T>(&self, serialize: F) {
+        let (lock, cvar) = &*self.shared;
+        lock.lock().unwrap().pending = Some(Box::new(move |path: &str| {
+            maybe_write_binary_durable(path, &serialize()).map_err(|err| err.to_string())?;
+            wrote_file(path);
+            Ok(())
+        }));
+        cvar.notify_one();
+    }
+
+    /// When the last successful save finished
+    pub fn last_saved(&self) -> Option<Instant> {
+        self.shared.0.lock().unwrap().last_saved
+    }
+
+    /// Why the most recent save failed, if it did. Cleared by the next successful save.
+    pub fn last_error(&self) -> Option<String> {
+        self.shared.0.lock().unwrap().error.clone()
+    }
+
+    /// Writes any unsaved change and stops the background thread, returning how the final save
+    /// went.
+    pub fn finish(mut self) -> Result<(), String> {
+        self.stop();
+        match self.last_error() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let (lock, cvar) = &*self.shared;
+            lock.lock().unwrap().shutdown = true;
+            cvar.notify_one();
+            if thread.join().is_err() {
+                warn!("Autosave thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for Autosaver {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn autosave_loop(path: String, interval: Duration, shared: Arc<(Mutex<AutosaveState>, Condvar)>) {
+    let (lock, cvar) = &*shared;
+    let mut state = lock.lock().unwrap();
+    loop {
+        while state.pending.is_none() && !state.shutdown {
+            state = cvar.wait(state).unwrap();
+        }
+        if state.pending.is_none() {
+            return;
+        }
+        // Let changes pile up until the interval since the last save is over. Shutting down skips
+        // the wait.
+        if let Some(deadline) = state.last_attempt.map(|t| t + interval) {
+            loop {
+                let now = Instant::now();
+                if state.shutdown || now >= deadline {
+                    break;
+                }
+                state = cvar.wait_timeout(state, deadline - now).unwrap().0;
+            }
+        }
+
+        let save = state.pending.take().unwrap();
+        state.last_attempt = Some(Instant::now());
+        // Don't hold the lock while writing, so mark_dirty never blocks on the disk
+        drop(state);
+        let result = save(&path);
+        state = lock.lock().unwrap();
+        match result {
+            Ok(()) => {
+                state.last_saved = Some(Instant::now());
+                state.error = None;
+            }
+            Err(err) => {
+                warn!("Autosave to {} failed: {}", path, err);
+                state.error = Some(err);
+            }
+        }
+    }
+}
+
+/// Like `write_binary`, but if a file already exists at `path`, keeps it as `path.bak`. The backup
+/// is only made once the new file has been written successfully, and both the backup and the new
+/// file are moved into place atomically.
+pub fn write_binary_with_backup<T: Serialize>(path: String, obj: &T) {
+    let mut txn = SaveTransaction::new();
+    if let Err(err) = txn
+        .write_binary(path.clone(), obj)
+        .and_then(|_| backup_then_commit(&path, txn))
+    {
+        panic!("Can't write_binary_with_backup({}): {}", path, err);
+    }
+}
+
+/// Like `write_json`, but keeps one generation of backup. See `write_binary_with_backup`.
+pub fn write_json_with_backup<T: Serialize>(path: String, obj: &T) {
+    let mut txn = SaveTransaction::new();
+    if let Err(err) = txn
+        .write_json(path.clone(), obj)
+        .and_then(|_| backup_then_commit(&path, txn))
+    {
+        panic!("Can't write_json_with_backup({}): {}", path, err);
+    }
+}
+
+fn backup_then_commit(path: &str, txn: SaveTransaction) -> Result<(), Box<dyn Error>> {
+    if Path::new(path).exists() {
+        // Copy instead of moving the original, so there's never a moment without a file at path
+        let backup = format!("{}.bak", path);
+        let tmp_backup = tempfile_name(&backup);
+        std::fs::copy(path, &tmp_backup)?;
+        std::fs::rename(&tmp_backup, &backup)?;
+    }
+    txn.commit()
+}
+
+/// Groups writes to several files, so that either all of them land on disk or none do. Each file
+/// is staged to a temporary file first; `commit` renames them all into place, putting back the
+/// files it already replaced if a rename fails. If staging any file fails, or the transaction is
+/// dropped without committing, the temporary files are deleted. The process dying in the middle of
+/// `commit` can still leave only some of the files replaced.
+pub struct SaveTransaction {
+    // (temporary path, final path)
+    staged: Vec<(String, String)>,
+}
+
+impl SaveTransaction {
+    pub fn new() -> SaveTransaction {
+        SaveTransaction { staged: Vec::new() }
+    }
+
+    pub fn write_binary<T: Serialize>(
+        &mut self,
+        path: String,
+        obj: &T,
+    ) -> Result<(), Box<dyn Error>> {
+        if !path.ends_with(".bin") {
+            panic!("write_binary needs {} to end with .bin", path);
+        }
+        self.stage(path, |file| {
+            encode_binary(file, obj, BinaryFormat::default())
+        })
+    }
+
+    pub fn write_json<T: Serialize>(
+        &mut self,
+        path: String,
+        obj: &T,
+    ) -> Result<(), Box<dyn Error>> {
+        if !path.ends_with(".json") {
+            panic!("write_json needs {} to end with .json", path);
+        }
+        self.stage(path, |file| {
+            JsonFormat::Compact.write_to(file, obj)?;
+            Ok(())
+        })
+    }
+
+    fn stage<F: FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn Error>>>(
+        &mut self,
+        path: String,
+        write: F,
+    ) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap())
+            .expect("Creating parent dir failed");
+        let tmp_path = tempfile_name(&path);
+        // Remember it before writing, so a partial file gets cleaned up too
+        self.staged.push((tmp_path.clone(), path));
+
+        let result = write_new_file(&tmp_path, write);
+        if result.is_err() {
+            self.rollback();
+        }
+        result
+    }
+
+    /// Moves every staged file into place. If a rename fails partway, the files already renamed
+    /// are restored to what they were before.
+    pub fn commit(mut self) -> Result<(), Box<dyn Error>> {
+        let mut done = Vec::new();
+        while !self.staged.is_empty() {
+            let (tmp_path, path) = self.staged.remove(0);
+            if let Err(err) = replace_file(&tmp_path, &path, &mut done) {
+                // Drop cleans up what's left
+                self.staged.insert(0, (tmp_path, path));
+                undo_replacements(done);
+                return Err(err);
+            }
+        }
+        for (path, old) in done {
+            if let Some(old) = old {
+                let _ = std::fs::remove_file(old);
+            }
+            wrote_file(&path);
+        }
+        Ok(())
+    }
+
+    fn rollback(&mut self) {
+        for (tmp_path, _) in self.staged.drain(..) {
+            let _ = std::fs::remove_file(tmp_path);
+        }
+    }
+}
+
+impl Drop for SaveTransaction {
+    fn drop(&mut self) {
+        self.rollback();
+    }
+}
+
+// Renames tmp_path over path, keeping a link to the version it replaces (if any) so that
+// undo_replacements can put it back. Records (path, old version) in `done`.
+fn replace_file(
+    tmp_path: &str,
+    path: &str,
+    done: &mut Vec<(String, Option<String>)>,
+) -> Result<(), Box<dyn Error>> {
+    let old = if Path::new(path).exists() {
+        let old = tempfile_name(path);
+        // Link instead of moving, so there's never a moment without a file at path
+        if std::fs::hard_link(path, &old).is_err() {
+            std::fs::copy(path, &old)?;
+        }
+        Some(old)
+    } else {
+        None
+    };
+    if let Err(err) = std::fs::rename(tmp_path, path) {
+        if let Some(old) = old {
+            let _ = std::fs::remove_file(old);
+        }
+        return Err(err.into());
+    }
+    done.push((path.to_string(), old));
+    Ok(())
+}
+
+fn undo_replacements(done: Vec<(String, Option<String>)>) {
+    for (path, old) in done.into_iter().rev() {
+        let result = match old {
+            Some(old) => std::fs::rename(&old, &path),
+            None => std::fs::remove_file(&path),
+        };
+        if let Err(err) = result {
+            warn!("Couldn't restore {} after a failed save: {}", path, err);
+        }
+    }
+}
+
+// A record file starts with the usual binary header for T. Each record is then a u64 length
+// (little-endian) followed by that many bytes of bincode.
+
+/// Appends records to a log file without rewriting what's already there. Read them back with
+/// `RecordReader`.
+pub struct RecordWriter<T: Serialize> {
+    file: File,
+    item_type: PhantomData<T>,
+}
+
+impl<T: Serialize> RecordWriter<T> {
+    /// Opens a record file for appending, creating it if needed. If an earlier run was
+    /// interrupted partway through writing a record, that partial record is dropped first.
+    pub fn open(path: String) -> Result<RecordWriter<T>, Box<dyn Error>> {
+        std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap())
+            .expect("Creating parent dir failed");
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)?;
+        let file_len = file.metadata()?.len();
+        if file_len == 0 {
+            file.write_all(&binary_header::<T>(BinaryFormat::Bincode))?;
+        } else {
+            let end = {
+                let (_, mut end, mut reader) =
+                    strip_binary_header::<T, _>(&path, BufReader::new(&file))?;
+                while let Some(len) = read_record_len(&mut reader, file_len.saturating_sub(end))? {
+                    std::io::copy(&mut (&mut reader).take(len), &mut std::io::sink())?;
+                    end += 8 + len;
+                }
+                end
+            };
+            if end != file_len {
+                warn!("{} ends with a partial record; dropping it", path);
+                file.set_len(end)?;
+            }
+            file.seek(SeekFrom::End(0))?;
+        }
+        Ok(RecordWriter {
+            file,
+            item_type: PhantomData,
+        })
+    }
+
+    /// Writes one record to the end of the file.
+    pub fn append(&mut self, item: &T) -> Result<(), Box<dyn Error>> {
+        let payload = bincode::serialize(item)?;
+        // One write, so a crash leaves at most one partial record
+        let mut record = (payload.len() as u64).to_le_bytes().to_vec();
+        record.extend(payload);
+        self.file.write_all(&record)?;
+        Ok(())
+    }
+}
+
+/// Reads the records written by `RecordWriter`, in order. A partial record at the end, left by an
+/// interrupted writer, is ignored.
+pub struct RecordReader<T: DeserializeOwned> {
+    reader: BinaryBody<BufReader<File>>,
+    // Bytes left in the file, to notice truncated records without trusting their length
+    remaining: u64,
+    done: bool,
+    item_type: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> RecordReader<T> {
+    pub fn open(path: String) -> Result<RecordReader<T>, ReadError> {
+        let file = File::open(&path)?;
+        let file_len = file.metadata()?.len();
+        let (_, header_len, reader) = strip_binary_header::<T, _>(&path, BufReader::new(file))?;
+        Ok(RecordReader {
+            reader,
+            remaining: file_len.saturating_sub(header_len),
+            done: false,
+            item_type: PhantomData,
+        })
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for RecordReader<T> {
+    type Item = Result<T, ReadError>;
+
+    fn next(&mut self) -> Option<Result<T, ReadError>> {
+        if self.done {
+            return None;
+        }
+        let len = match read_record_len(&mut self.reader, self.remaining) {
+            Ok(Some(len)) => len,
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err.into()));
+            }
+        };
+        self.remaining -= 8 + len;
+        let mut payload = vec![0; len as usize];
+        if let Err(err) = self.reader.read_exact(&mut payload) {
+            self.done = true;
+            return Some(Err(err.into()));
+        }
+        // A record that doesn't decode doesn't affect the ones after it
+        Some(bincode::deserialize(&payload).map_err(|err| ReadError::Deserialize(err.to_string())))
+    }
+}
+
+// Reads the length of the next record, or None if there isn't a whole one left in `remaining`
+// bytes.
+fn read_record_len<R: Read>(reader: &mut R, remaining: u64) -> std::io::Result<Option<u64>> {
+    if remaining < 8 {
+        return Ok(None);
+    }
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    if len > remaining - 8 {
+        return Ok(None);
+    }
+    Ok(Some(len))
+}
+
+// An appendable JSON array keeps one element per line between an opening "[" line and a closing
+// "]" line. Compact JSON never contains a raw newline, so a line that isn't followed by one is a
+// partial write.
+
+/// A JSON array on disk that many threads can append records to. Each append is a single write
+/// that replaces the closing bracket, so a process killed partway through leaves at most one
+/// partial record at the end. Opening the file again drops it and closes the array, and the result
+/// always reads back as a plain `Vec<T>` with `read_json`.
+pub struct AppendableJsonArray<T: Serialize> {
+    path: String,
+    inner: Mutex<AppendState>,
+    // fn(&T) so sharing the array between threads doesn't need T: Sync
+    item_type: PhantomData<fn(&T)>,
+}
+
+struct AppendState {
+    file: File,
+    // Where the closing "\n]\n" starts
+    end: u64,
+    len: usize,
+}
+
+impl<T: Serialize> AppendableJsonArray<T> {
+    /// Opens an array for appending, creating it if needed. Anything after the last complete
+    /// record is dropped.
+    pub fn open(path: String) -> Result<AppendableJsonArray<T>, Box<dyn Error>> {
+        std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap())
+            .expect("Creating parent dir failed");
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        // Also covers a file that was only partly created
+        let (end, len) = if "[\n]\n".starts_with(contents.as_str()) {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(b"[\n]\n")?;
+            (1, 0)
+        } else {
+            let (end, len, closed) = scan_json_array(&path, &contents)?;
+            if !closed {
+                warn!(
+                    "{} wasn't closed properly; keeping the first {} records",
+                    path,
+                    prettyprint_usize(len)
+                );
+                file.set_len(end)?;
+                file.seek(SeekFrom::Start(end))?;
+                file.write_all(b"\n]\n")?;
+            }
+            (end, len)
+        };
+        Ok(AppendableJsonArray {
+            path,
+            inner: Mutex::new(AppendState { file, end, len }),
+            item_type: PhantomData,
+        })
+    }
+
+    /// Adds one record to the end of the array. Safe to call from many threads at once.
+    pub fn append(&self, record: &T) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_string(record)?;
+        let mut state = self.inner.lock().unwrap();
+        let separator = if state.len == 0 { "\n" } else { ",\n" };
+        let end = state.end;
+        state.file.seek(SeekFrom::Start(end))?;
+        state
+            .file
+            .write_all(format!("{}{}\n]\n", separator, json).as_bytes())?;
+        let new_end = end + (separator.len() + json.len()) as u64;
+        // If an earlier append failed partway, it may have left something longer behind
+        state.file.set_len(new_end + 3)?;
+        state.end = new_end;
+        state.len += 1;
+        Ok(())
+    }
+
+    /// How many records the array holds.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+// Returns where the last complete record ends, how many records there are, and whether the array
+// is closed with nothing after it.
+fn scan_json_array(path: &str, contents: &str) -> Result<(u64, usize, bool), Box<dyn Error>> {
+    if !contents.starts_with("[\n") {
+        return Err(format!("{} isn't an appendable JSON array", path).into());
+    }
+    let mut end = 1;
+    let mut len = 0;
+    let mut offset = 2;
+    // The last piece isn't followed by a newline, so it never counts
+    let mut lines = contents[2..].split('\n').collect::<Vec<_>>();
+    lines.pop();
+    for line in lines {
+        if line == "]" {
+            let closed = offset + 2 == contents.len();
+            return Ok((end as u64, len, closed));
+        }
+        let record = line.strip_suffix(',').unwrap_or(line);
+        if serde_json::from_str::<serde_json::Value>(record).is_err() {
+            break;
+        }
+        end = offset + record.len();
+        len += 1;
+        offset += line.len() + 1;
+    }
+    Ok((end as u64, len, false))
+}
+
+fn write_new_file<F: FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn Error>>>(
+    path: &str,
+    write: F,
+) -> Result<(), Box<dyn Error>> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write(&mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn maybe_write_csv<T: Serialize>(path: &str, rows: &[T]) -> Result<(), Box<dyn Error>> {
+    if !path.ends_with(".csv") {
+        panic!("write_csv needs {} to end with .csv", path);
+    }
+
+    std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())
+        .expect("Creating parent dir failed");
+
+    // The header row comes from the field names of T
+    let mut writer = csv::Writer::from_path(path)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_csv<T: Serialize>(path: String, rows: &[T]) {
+    if let Err(err) = maybe_write_csv(&path, rows) {
+        panic!("Can't write_csv({}): {}", path, err);
+    }
+    wrote_file(&path);
 }
 
 /// Idempotent
@@ -104,6 +1192,157 @@ pub fn delete_file<I: Into<String>>(path: I) {
     }
 }
 
+/// Moves a file to the OS trash, so the user can get it back. Where there's no trash, the file is
+/// deleted permanently instead, with a warning. Use `delete_file` for temporary files that nobody
+/// would want back.
+pub fn trash_file<I: Into<String>>(path: I) {
+    let path = path.into();
+    #[cfg(any(windows, target_os = "macos", target_os = "linux"))]
+    {
+        match trash::remove(&path) {
+            Ok(()) => {
+                println!("Moved {} to the trash", path);
+                return;
+            }
+            Err(err) => {
+                warn!(
+                    "Couldn't move {} to the trash, so deleting it instead: {:?}",
+                    path, err
+                );
+            }
+        }
+    }
+    #[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+    {
+        warn!(
+            "There's no trash on this platform, so permanently deleting {}",
+            path
+        );
+    }
+    delete_file(path);
+}
+
+// Set this to a file path to append a line there for every file written. Off by default.
+const SAVE_LOG_VAR: &str = "ABST_SAVE_LOG";
+// Past this size, the log is moved to `<log>.old` and a fresh one started
+const SAVE_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+fn wrote_file(path: &str) {
+    println!("Wrote {}", path);
+    if let Ok(log_path) = std::env::var(SAVE_LOG_VAR) {
+        if let Err(err) = append_save_log(&log_path, path) {
+            warn!("Couldn't update save log {}: {}", log_path, err);
+        }
+    }
+}
+
+// Each line has the Unix timestamp, path, and size in bytes, separated by tabs.
+fn append_save_log(log_path: &str, path: &str) -> Result<(), Box<dyn Error>> {
+    if std::fs::metadata(log_path)
+        .map(|m| m.len() > SAVE_LOG_MAX_BYTES)
+        .unwrap_or(false)
+    {
+        std::fs::rename(log_path, format!("{}.old", log_path))?;
+    }
+    let bytes = std::fs::metadata(path)?.len();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+    writeln!(file, "{}\t{}\t{}", now, path, bytes)?;
+    Ok(())
+}
+
+/// Streams everything from `src` into a new file at `dest_path`, printing progress along the way.
+/// If `total_bytes` isn't known, just the throughput is shown. The bytes go to a temporary file
+/// first, which is only renamed to `dest_path` once everything has been copied, so a failed or
+/// interrupted copy never leaves a partial file behind.
+pub fn copy_with_progress<R: Read>(
+    mut src: R,
+    dest_path: &str,
+    total_bytes: Option<usize>,
+    timer: &mut Timer,
+) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(std::path::Path::new(dest_path).parent().unwrap())
+        .expect("Creating parent dir failed");
+    let tmp_path = tempfile_name(dest_path);
+
+    let started_at = Instant::now();
+    let processed_bytes =
+        match inner_copy_with_progress(&mut src, &tmp_path, dest_path, total_bytes, started_at) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                // Don't leave the partial file around
+                let _ = std::fs::remove_file(&tmp_path);
+                return Err(err);
+            }
+        };
+    std::fs::rename(&tmp_path, dest_path)?;
+
+    let elapsed = elapsed_seconds(started_at);
+    let line = format!(
+        "Copied {} ({} MB)... {}",
+        dest_path,
+        prettyprint_usize(processed_bytes / 1024 / 1024),
+        prettyprint_time(elapsed)
+    );
+    clear_current_line();
+    println!("{}", line);
+    timer.add_result(elapsed, line);
+    Ok(())
+}
+
+// Returns the number of bytes copied
+fn inner_copy_with_progress<R: Read>(
+    src: &mut R,
+    tmp_path: &str,
+    dest_path: &str,
+    total_bytes: Option<usize>,
+    started_at: Instant,
+) -> Result<usize, Box<dyn Error>> {
+    let mut file = BufWriter::new(File::create(tmp_path)?);
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut processed_bytes = 0;
+    let mut last_printed_at = started_at;
+    loop {
+        let bytes = src.read(&mut buf)?;
+        if bytes == 0 {
+            break;
+        }
+        file.write_all(&buf[0..bytes])?;
+        processed_bytes += bytes;
+
+        if elapsed_seconds(last_printed_at) >= PROGRESS_FREQUENCY_SECONDS {
+            last_printed_at = Instant::now();
+            let elapsed = elapsed_seconds(started_at);
+            clear_current_line();
+            if let Some(total) = total_bytes {
+                print!(
+                    "Copying to {}: {}/{} MB... {}",
+                    dest_path,
+                    prettyprint_usize(processed_bytes / 1024 / 1024),
+                    prettyprint_usize(total / 1024 / 1024),
+                    prettyprint_time(elapsed)
+                );
+            } else {
+                print!(
+                    "Copying to {}: {} MB ({:.1} MB/s)... {}",
+                    dest_path,
+                    prettyprint_usize(processed_bytes / 1024 / 1024),
+                    (processed_bytes as f64) / 1024.0 / 1024.0 / elapsed,
+                    prettyprint_time(elapsed)
+                );
+            }
+            stdout().flush().unwrap();
+        }
+    }
+    file.flush()?;
+    Ok(processed_bytes)
+}
+
 // TODO I'd like to get rid of this and just use Timer.read_file, but external libraries consume
 // the reader. :\
 pub struct FileWithProgress {
@@ -189,3 +1428,718 @@ impl Read for FileWithProgress {
         Ok(bytes)
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// A scratch directory that's removed when the test ends, even if an assertion fails first.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir =
+                std::env::temp_dir().join(format!("abstutil_{}_{}", name, std::process::id()));
+            // Left over from a run that was killed
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn name(&self) -> String {
+            self.0.to_str().unwrap().to_string()
+        }
+
+        fn path(&self, file: &str) -> String {
+            self.0.join(file).to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn list_dir_skips_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new("list_dir");
+        File::create(dir.path("fine.json")).unwrap();
+        File::create(dir.0.join(OsStr::from_bytes(b"bad\xff.json"))).unwrap();
+
+        assert_eq!(list_dir(dir.name()), vec![dir.path("fine.json")]);
+    }
+
+    #[test]
+    fn scan_integrity_finds_broken_files() {
+        let dir = TempDir::new("scan");
+        std::fs::create_dir_all(dir.path("nested")).unwrap();
+        std::fs::write(dir.path("good.json"), "{\"x\": 1}").unwrap();
+        std::fs::write(dir.path("nested/broken.json"), "{\"x\": ").unwrap();
+        write_binary(dir.path("good.bin"), &vec![1_usize, 2, 3]);
+        std::fs::copy(dir.path("good.bin"), dir.path("binary.json")).unwrap();
+
+        let results = scan_integrity(dir.name());
+        let status = |name: &str| {
+            results
+                .iter()
+                .find(|(p, _)| p.ends_with(name))
+                .map(|(_, status)| status.clone())
+                .unwrap()
+        };
+        assert_eq!(results.len(), 4);
+        assert_eq!(status("good.json"), IntegrityStatus::Ok);
+        assert_eq!(status("good.bin"), IntegrityStatus::Ok);
+        assert_eq!(status("binary.json"), IntegrityStatus::WrongExtension);
+        assert!(matches!(status("broken.json"), IntegrityStatus::Corrupt(_)));
+    }
+
+    #[test]
+    fn reading_the_wrong_type_fails() {
+        let dir = TempDir::new("type_tag");
+        let path = dir.path("numbers.bin");
+        write_binary(path.clone(), &vec![1_usize, 2, 3]);
+        let mut timer = Timer::throwaway();
+        let ok: Result<Vec<usize>, ReadError> = try_read_binary(path.clone(), &mut timer);
+        assert_eq!(ok.unwrap(), vec![1, 2, 3]);
+        // Writing through a reference doesn't change the tag
+        let numbers = vec![4_usize];
+        write_binary(path.clone(), &&numbers);
+        let borrowed: Result<Vec<usize>, ReadError> = try_read_binary(path.clone(), &mut timer);
+        assert_eq!(borrowed.unwrap(), numbers);
+        let wrong: Result<String, ReadError> = try_read_binary(path, &mut timer);
+        assert!(wrong
+            .unwrap_err()
+            .to_string()
+            .contains("expected String, file contains Vec<usize>"));
+    }
+
+    #[test]
+    fn failed_reads_leave_the_timer_balanced() {
+        let dir = TempDir::new("failed_read");
+        let path = dir.path("numbers.bin");
+        write_binary(path.clone(), &vec![1_usize, 2, 3]);
+        let mut timer = Timer::new("failed read");
+        timer.start("load");
+        let wrong: Result<Vec<(String, String)>, ReadError> = try_read_binary(path, &mut timer);
+        assert!(wrong.is_err());
+        // Would panic if the file were still on the stack
+        timer.stop("load");
+    }
+
+    #[test]
+    fn huge_length_prefixes_are_rejected() {
+        let dir = TempDir::new("limit");
+        let path = dir.path("huge.bin");
+        // A string claiming to be a terabyte long, followed by nothing
+        let mut bytes = binary_header::<String>(BinaryFormat::Bincode);
+        bytes.extend_from_slice(&(1_u64 << 40).to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+        let mut timer = Timer::throwaway();
+        let result: Result<String, ReadError> = try_read_binary(path, &mut timer);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("more than the limit"));
+    }
+
+    #[test]
+    fn mixed_directories_keep_what_they_cant_parse() {
+        let dir = TempDir::new("mixed");
+        std::fs::write(dir.path("number.json"), "1").unwrap();
+        std::fs::write(dir.path("other.json"), "{\"x\": 2}").unwrap();
+        write_binary(dir.path("words.bin"), &"hello".to_string());
+
+        let kept: Vec<(String, Result<usize, RawData>)> = load_all_objects_or_raw(dir.name(), true);
+        let dropped: Vec<(String, Result<usize, RawData>)> =
+            load_all_objects_or_raw(dir.name(), false);
+
+        let names: Vec<&str> = kept.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["number", "other", "words"]);
+        assert_eq!(kept[0].1.as_ref().unwrap(), &1);
+        match &kept[1].1 {
+            Err(RawData {
+                contents: Some(RawContents::Json(value)),
+                ..
+            }) => assert_eq!(value["x"], 2),
+            x => panic!("other.json came back as {:?}", x),
+        }
+        assert!(matches!(
+            kept[2].1,
+            Err(RawData {
+                contents: Some(RawContents::Bytes(_)),
+                ..
+            })
+        ));
+        assert!(dropped[1..]
+            .iter()
+            .all(|(_, result)| matches!(result, Err(RawData { contents: None, .. }))));
+    }
+
+    #[test]
+    fn par_map_files_keeps_going_past_broken_files() {
+        let dir = TempDir::new("par_map");
+        for x in 0..10 {
+            std::fs::write(dir.path(&format!("{:02}.json", x)), x.to_string()).unwrap();
+        }
+        std::fs::write(dir.path("broken.json"), "{").unwrap();
+
+        let results = par_map_files(dir.name(), &mut Timer::throwaway(), |name, x: usize| {
+            format!("{}:{}", name, x * x)
+        });
+
+        assert_eq!(results.len(), 11);
+        for (x, (name, result)) in results.iter().take(10).enumerate() {
+            assert_eq!(name, &format!("{:02}", x));
+            assert_eq!(result.as_ref().unwrap(), &format!("{:02}:{}", x, x * x));
+        }
+        assert_eq!(results[10].0, "broken");
+        assert!(matches!(results[10].1, Err(ReadError::Deserialize(_))));
+    }
+
+    #[test]
+    fn object_pages() {
+        let dir = TempDir::new("pages");
+        for name in &["e", "b", "d", "a", "c"] {
+            File::create(dir.path(&format!("{}.json", name))).unwrap();
+        }
+
+        assert_eq!(
+            list_objects_page(dir.name(), 1, 2),
+            (vec!["b".to_string(), "c".to_string()], 5)
+        );
+        assert_eq!(
+            list_objects_page(dir.name(), 4, 10),
+            (vec!["e".to_string()], 5)
+        );
+        assert_eq!(list_objects_page(dir.name(), 10, 10), (Vec::new(), 5));
+
+        // The directory only just changed, so a new file shows up right away
+        File::create(dir.path("f.json")).unwrap();
+        assert_eq!(
+            list_objects_page(dir.name(), 5, 1),
+            (vec!["f".to_string()], 6)
+        );
+    }
+
+    #[test]
+    fn optional_files() {
+        let dir = TempDir::new("optional");
+        let mut timer = Timer::throwaway();
+
+        write_binary(dir.path("present.bin"), &vec![1_usize, 2, 3]);
+        std::fs::write(dir.path("corrupt.bin"), b"not bincode").unwrap();
+        std::fs::write(dir.path("present.json"), "[1, 2, 3]").unwrap();
+        std::fs::write(dir.path("corrupt.json"), "[1, 2,").unwrap();
+
+        let missing: Result<Option<Vec<usize>>, ReadError> =
+            read_binary_optional(dir.path("missing.bin"), &mut timer);
+        assert!(matches!(missing, Ok(None)));
+        let present: Option<Vec<usize>> =
+            read_binary_optional(dir.path("present.bin"), &mut timer).unwrap();
+        assert_eq!(present, Some(vec![1, 2, 3]));
+        assert!(read_binary_optional::<Vec<usize>>(dir.path("corrupt.bin"), &mut timer).is_err());
+
+        let missing: Result<Option<Vec<usize>>, ReadError> =
+            read_json_optional(dir.path("missing.json"), &mut timer);
+        assert!(matches!(missing, Ok(None)));
+        let present: Option<Vec<usize>> =
+            read_json_optional(dir.path("present.json"), &mut timer).unwrap();
+        assert_eq!(present, Some(vec![1, 2, 3]));
+        assert!(matches!(
+            read_json_optional::<Vec<usize>>(dir.path("corrupt.json"), &mut timer),
+            Err(ReadError::Deserialize(_))
+        ));
+    }
+
+    #[test]
+    fn content_addressed_blobs_are_shared() {
+        let dir = TempDir::new("cas");
+        std::fs::write(dir.path("notes.bin"), "not a blob").unwrap();
+
+        let mut manifest = CasManifest::default();
+        let shared = write_binary_cas(dir.name(), &vec![1_usize, 2, 3]);
+        manifest.entries.insert("first".to_string(), shared.clone());
+        manifest.entries.insert(
+            "second".to_string(),
+            write_binary_cas(dir.name(), &vec![1_usize, 2, 3]),
+        );
+        let other = write_binary_cas(dir.name(), &vec![4_usize]);
+        assert_eq!(manifest.entries["second"], shared);
+        assert_ne!(other, shared);
+        assert_eq!(list_dir(dir.name()).len(), 3);
+
+        let loaded: Vec<usize> =
+            read_binary(cas_path(&dir.name(), &shared), &mut Timer::throwaway());
+        assert_eq!(loaded, vec![1, 2, 3]);
+
+        assert_eq!(gc_cas(dir.name(), &manifest.live_hashes()), 1);
+        // Hex digits sort before "notes"
+        assert_eq!(
+            list_all_objects(dir.name()),
+            vec![shared, "notes".to_string()]
+        );
+    }
+
+    #[test]
+    fn compressed_files_round_trip() {
+        let dir = TempDir::new("compressed");
+        let path = dir.path("data.bin.gz");
+        let data = vec![42_usize; 10_000];
+        let mut timer = Timer::throwaway();
+
+        write_binary_compressed(path.clone(), &data, Compression::Gzip(1));
+        assert!(std::fs::metadata(&path).unwrap().len() < 1000);
+        let fast: Vec<usize> = read_object(path.clone(), &mut timer).unwrap();
+        assert_eq!(fast, data);
+        write_binary_compressed(path.clone(), &data, Compression::default());
+        let balanced: Vec<usize> = try_read_object(path.clone(), &mut timer).unwrap();
+        assert_eq!(balanced, data);
+        let too_small: Result<Vec<usize>, ReadError> =
+            try_read_compressed_binary(path, BinaryLimit::Bytes(100), &mut timer);
+        assert!(too_small.is_err());
+    }
+
+    #[test]
+    fn autosaves_coalesce_changes() {
+        let dir = TempDir::new("autosave");
+        let path = dir.path("state.bin");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let count_call = |value: usize| {
+            let calls = calls.clone();
+            move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                value
+            }
+        };
+
+        // Nothing's been saved yet, so the first change is written right away
+        let saver = Autosaver::new(path.clone(), Duration::from_secs(3600));
+        saver.mark_dirty(count_call(1));
+        let started = Instant::now();
+        while saver.last_saved().is_none() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "first save never happened"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // The next one has to wait an hour, so these pile up until the final flush
+        saver.mark_dirty(count_call(2));
+        saver.mark_dirty(count_call(3));
+        saver.finish().unwrap();
+
+        let saved: usize = read_binary(path, &mut Timer::throwaway());
+        assert_eq!(saved, 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn failed_commits_put_back_what_they_replaced() {
+        let dir = TempDir::new("txn");
+        std::fs::create_dir_all(dir.path("b.json")).unwrap();
+        // Renaming a file over a non-empty directory fails
+        std::fs::write(dir.path("b.json/x"), "x").unwrap();
+        let a = dir.path("a.json");
+        std::fs::write(&a, "\"old\"").unwrap();
+
+        let mut txn = SaveTransaction::new();
+        txn.write_json(a.clone(), &"new").unwrap();
+        txn.write_json(dir.path("b.json"), &"new").unwrap();
+        assert!(txn.commit().is_err());
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "\"old\"");
+        // No temporary files left behind
+        assert_eq!(std::fs::read_dir(&dir.0).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn saves_refuse_to_clobber_other_changes() {
+        let dir = TempDir::new("conflict");
+        let path = dir.path("state.bin");
+
+        let version = write_binary_if_not_newer(path.clone(), &1_usize, None).unwrap();
+        // Somebody else saved after we loaded
+        let stale = FileVersion {
+            mtime: version.mtime - Duration::from_secs(1),
+            ..version.clone()
+        };
+        assert!(matches!(
+            write_binary_if_not_newer(path.clone(), &2_usize, Some(stale)),
+            Err(WriteError::Conflict { .. })
+        ));
+        // In the same timestamp tick, and without changing the size
+        let same_tick = FileVersion {
+            md5: "something else".to_string(),
+            ..version.clone()
+        };
+        assert!(matches!(
+            write_binary_if_not_newer(path.clone(), &2_usize, Some(same_tick)),
+            Err(WriteError::Conflict { .. })
+        ));
+        // Or created the file when we thought there wasn't one
+        assert!(matches!(
+            write_binary_if_not_newer(path.clone(), &3_usize, None),
+            Err(WriteError::Conflict { expected: None, .. })
+        ));
+        assert!(write_binary_if_not_newer(path.clone(), &4_usize, Some(version)).is_ok());
+
+        let saved: usize = read_binary(path, &mut Timer::throwaway());
+        assert_eq!(saved, 4);
+    }
+
+    #[test]
+    fn resumable_loads_skip_what_was_already_loaded() {
+        let dir = TempDir::new("resume");
+        std::fs::create_dir_all(dir.path("data")).unwrap();
+        std::fs::write(dir.path("data/a.json"), "1").unwrap();
+        std::fs::write(dir.path("data/b.json"), "2").unwrap();
+        let data = dir.path("data");
+        let manifest = dir.path("manifest.json");
+
+        let first: Vec<(String, usize)> =
+            load_all_objects_resumable(data.clone(), manifest.clone(), None);
+        assert_eq!(first, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+        // Pretend the first call was cut off after "a". Its value comes from the partial result,
+        // not the file.
+        std::fs::write(&manifest, "[\"a\"]").unwrap();
+        let resumed: Vec<(String, usize)> =
+            load_all_objects_resumable(data, manifest.clone(), Some(vec![("a".to_string(), 100)]));
+        assert_eq!(resumed, vec![("a".to_string(), 100), ("b".to_string(), 2)]);
+        let recorded = std::fs::read_to_string(&manifest).unwrap();
+        assert!(recorded.contains("\"a\"") && recorded.contains("\"b\""));
+    }
+
+    #[test]
+    fn read_binary_arc_shares_live_data() {
+        use std::sync::Arc;
+
+        let dir = TempDir::new("arc");
+        let path = dir.path("numbers.bin");
+        write_binary(path.clone(), &vec![1_usize, 2, 3]);
+        let mut timer = Timer::throwaway();
+
+        let first: Arc<Vec<usize>> = read_binary_arc(path.clone(), &mut timer).unwrap();
+        let second: Arc<Vec<usize>> = read_binary_arc(path.clone(), &mut timer).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Once nobody holds it, the next load reads the file again
+        drop(first);
+        drop(second);
+        write_binary(path.clone(), &vec![4_usize, 5]);
+        let third: Arc<Vec<usize>> = read_binary_arc(path, &mut timer).unwrap();
+        assert_eq!(*third, vec![4, 5]);
+    }
+
+    #[test]
+    fn tempfile_names_are_unique_and_reproducible() {
+        let path = "/tmp/abc.bin";
+        seed_tempfile_names(42);
+        let first: Vec<String> = (0..3).map(|_| tempfile_name(path)).collect();
+        seed_tempfile_names(42);
+        let second: Vec<String> = (0..3).map(|_| tempfile_name(path)).collect();
+        assert_eq!(first, second);
+        assert_eq!(
+            first
+                .iter()
+                .collect::<std::collections::BTreeSet<_>>()
+                .len(),
+            3
+        );
+        assert!(first[0].starts_with(&format!("{}.tmp.{}.", path, std::process::id())));
+
+        // Other threads writing the same target get their own names
+        let mut handles = Vec::new();
+        for _ in 0..4 {
+            handles.push(std::thread::spawn(move || {
+                seed_tempfile_names(42);
+                tempfile_name(path)
+            }));
+        }
+        let mut names: std::collections::BTreeSet<String> =
+            handles.into_iter().map(|h| h.join().unwrap()).collect();
+        names.insert(tempfile_name(path));
+        assert_eq!(names.len(), 5);
+    }
+
+    #[test]
+    fn record_files_survive_an_interrupted_append() {
+        let dir = TempDir::new("records");
+        let path = dir.path("records.bin");
+        let mut writer = RecordWriter::open(path.clone()).unwrap();
+        for x in &["one", "two", "three"] {
+            writer.append(&x.to_string()).unwrap();
+        }
+        drop(writer);
+
+        // The start of a record that never finished
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&100_u64.to_le_bytes()).unwrap();
+        file.write_all(b"fo").unwrap();
+        drop(file);
+        let read = |path: &str| -> Vec<String> {
+            RecordReader::open(path.to_string())
+                .unwrap()
+                .map(|x| x.unwrap())
+                .collect()
+        };
+        assert_eq!(read(&path), vec!["one", "two", "three"]);
+
+        // Appending again starts where the last complete record ended
+        RecordWriter::open(path.clone())
+            .unwrap()
+            .append(&"four".to_string())
+            .unwrap();
+        assert_eq!(read(&path), vec!["one", "two", "three", "four"]);
+    }
+
+    #[test]
+    fn headerless_record_files_keep_every_record() {
+        let dir = TempDir::new("old_records");
+        let path = dir.path("records.bin");
+        // Written before record files had a header
+        let mut bytes = Vec::new();
+        for x in &["one", "two"] {
+            let payload = bincode::serialize(&x.to_string()).unwrap();
+            bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+            bytes.extend(payload);
+        }
+        std::fs::write(&path, bytes).unwrap();
+
+        RecordWriter::open(path.clone())
+            .unwrap()
+            .append(&"three".to_string())
+            .unwrap();
+        let all: Vec<String> = RecordReader::open(path)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(all, vec!["one", "two", "three"]);
+    }
+
+    #[test]
+    fn json_arrays_take_appends_from_many_threads() {
+        let dir = TempDir::new("appends");
+        let path = dir.path("records.json");
+        let array = Arc::new(AppendableJsonArray::open(path.clone()).unwrap());
+        let threads = (0..4_usize)
+            .map(|thread| {
+                let array = array.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        array.append(&(thread, format!("line\n{}", i))).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(array.len(), 100);
+        drop(array);
+        let mut records: Vec<(usize, String)> = read_json(path.clone(), &mut Timer::throwaway());
+        records.sort();
+        assert_eq!(records.len(), 100);
+        assert_eq!(records[0], (0, "line\n0".to_string()));
+
+        // Killed partway through an append
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::End(-3)).unwrap();
+        file.write_all(b",\n[4, \"li").unwrap();
+        drop(file);
+        let array = AppendableJsonArray::<(usize, String)>::open(path.clone()).unwrap();
+        assert_eq!(array.len(), 100);
+        array.append(&(4, "last".to_string())).unwrap();
+        drop(array);
+        let records: Vec<(usize, String)> = read_json(path, &mut Timer::throwaway());
+        assert_eq!(records.len(), 101);
+        assert_eq!(records[100], (4, "last".to_string()));
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Row {
+        name: String,
+        count: usize,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn csv_files_round_trip() {
+        let dir = TempDir::new("csv");
+        let path = dir.path("rows.csv");
+        let rows = vec![
+            Row {
+                name: "Rainier Ave, south".to_string(),
+                count: 3,
+                note: Some("says \"closed\"\non two lines".to_string()),
+            },
+            Row {
+                name: "Aurora".to_string(),
+                count: 0,
+                note: None,
+            },
+        ];
+        write_csv(path.clone(), &rows);
+        assert_eq!(read_csv::<Row>(path.clone()).unwrap(), rows);
+
+        // Columns are matched by the header, not by position
+        std::fs::write(&path, "count,note,name\n5,,Broadway\n").unwrap();
+        assert_eq!(
+            read_csv::<Row>(path.clone()).unwrap(),
+            vec![Row {
+                name: "Broadway".to_string(),
+                count: 5,
+                note: None,
+            }]
+        );
+        std::fs::write(&path, "name,count,note\nBroadway,lots,\n").unwrap();
+        assert!(read_csv::<Row>(path).is_err());
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Prefs {
+        query: String,
+        layers: Vec<String>,
+        camera: Camera,
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Camera {
+        zoom: f64,
+        follow: bool,
+    }
+
+    impl Default for Prefs {
+        fn default() -> Prefs {
+            Prefs {
+                query: String::new(),
+                layers: vec!["roads".to_string(), "parcels".to_string()],
+                camera: Camera {
+                    zoom: 1.0,
+                    follow: true,
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn lenient_json_fills_missing_fields_and_ignores_extra_ones() {
+        let dir = TempDir::new("lenient");
+        let path = dir.path("prefs.json");
+        let mut timer = Timer::throwaway();
+
+        // Written before "layers" and "camera.follow" existed, and after "theme" was removed
+        std::fs::write(
+            &path,
+            "{\"query\": \"parking\", \"camera\": {\"zoom\": 3.0}, \"theme\": \"dark\"}",
+        )
+        .unwrap();
+        let prefs: Prefs = read_json_lenient(path.clone(), &mut timer).unwrap();
+        assert_eq!(
+            prefs,
+            Prefs {
+                query: "parking".to_string(),
+                camera: Camera {
+                    zoom: 3.0,
+                    follow: true,
+                },
+                ..Prefs::default()
+            }
+        );
+        // A strict read of the same file fails
+        assert!(try_read_json::<Prefs>(path.clone(), &mut timer).is_err());
+
+        // Lists aren't merged element by element
+        std::fs::write(&path, "{\"layers\": []}").unwrap();
+        let prefs: Prefs = read_json_lenient(path.clone(), &mut timer).unwrap();
+        assert!(prefs.layers.is_empty());
+
+        // Fields that are present still have to have the right type
+        std::fs::write(&path, "{\"query\": 5}").unwrap();
+        assert!(matches!(
+            read_json_lenient::<Prefs>(path.clone(), &mut timer),
+            Err(ReadError::Deserialize(_))
+        ));
+        assert!(read_json_lenient::<Prefs>(dir.path("missing.json"), &mut timer).is_err());
+    }
+
+    mod v1 {
+        #[derive(serde::Serialize)]
+        pub struct Settings {
+            pub name: String,
+            pub retired: bool,
+        }
+    }
+
+    mod v2 {
+        #[derive(serde::Deserialize)]
+        pub struct Settings {
+            pub name: String,
+            #[serde(default)]
+            pub layers: Vec<String>,
+        }
+    }
+
+    #[test]
+    fn messagepack_tolerates_changed_fields() {
+        let dir = TempDir::new("msgpack");
+        let path = dir.path("settings.bin");
+        let old = v1::Settings {
+            name: "seattle".to_string(),
+            retired: true,
+        };
+        write_binary_with(path.clone(), &old, BinaryFormat::MessagePack);
+        let mut timer = Timer::throwaway();
+        // A field was removed and another added since the file was written
+        let result: Result<v2::Settings, ReadError> = try_read_binary(path, &mut timer);
+        let new = result.unwrap();
+        assert_eq!(new.name, "seattle");
+        assert!(new.layers.is_empty());
+    }
+
+    #[test]
+    fn repair_directory_renames_and_regenerates_checksums() {
+        let dir = TempDir::new("repair");
+        let path = |name: &str| dir.path(name);
+        write_binary(path("binary.bin"), &vec![1_usize, 2, 3]);
+        std::fs::copy(path("binary.bin"), path("misnamed.json")).unwrap();
+        std::fs::write(path("broken.json"), "{\"x\": ").unwrap();
+        let dir_name = dir.name();
+
+        // Without asking, files lacking a checksum don't get one
+        assert!(!repair_directory(dir_name.clone(), true, false)
+            .iter()
+            .any(|a| matches!(a, RepairAction::RegeneratedChecksum(_))));
+
+        let preview = repair_directory(dir_name.clone(), true, true);
+        assert!(!file_exists(path("binary.bin.md5")));
+        assert!(file_exists(path("misnamed.json")));
+
+        let actions = repair_directory(dir_name.clone(), false, true);
+        assert_eq!(preview, actions);
+        assert!(actions.contains(&RepairAction::RegeneratedChecksum(path("binary.bin"))));
+        assert!(actions.contains(&RepairAction::Renamed {
+            from: path("misnamed.json"),
+            to: path("misnamed.bin"),
+        }));
+        assert!(actions.iter().any(|a| match a {
+            RepairAction::Unrepairable { path, .. } => path.ends_with("broken.json"),
+            _ => false,
+        }));
+        assert!(scan_integrity(dir_name.clone())
+            .into_iter()
+            .all(|(p, status)| p.ends_with("broken.json") || status == IntegrityStatus::Ok));
+
+        // The renamed file gets its checksum on the next pass
+        let again = repair_directory(dir_name, false, true);
+        assert_eq!(again.len(), 2);
+        assert!(again.contains(&RepairAction::RegeneratedChecksum(path("misnamed.bin"))));
+    }
+}
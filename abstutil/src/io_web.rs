@@ -2,12 +2,13 @@
 //! the WASM binary using include_dir. For now, no support for saving files.
 
 use std::collections::BTreeSet;
+use std::error::Error;
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 pub use crate::io::*;
-use crate::{path_player, to_json, Manifest, Timer};
+use crate::{path_player, JsonFormat, Manifest, Timer};
 
 // Bring in everything from data/system/ matching one of the prefixes -- aka, no scenarios, and
 // only the smallest map. Everything else has to be dynamically loaded over HTTP.
@@ -36,6 +37,11 @@ pub fn file_exists<I: Into<String>>(path: I) -> bool {
             .contains_key(path.trim_start_matches("../"))
 }
 
+/// There are no modification times to compare on web, so caches are only stale when missing.
+pub fn is_cache_stale(cache_path: &str, _inputs: &[String]) -> bool {
+    !file_exists(cache_path)
+}
+
 pub fn list_dir(dir: String) -> Vec<String> {
     // TODO Handle player data in local storage
     let mut results = BTreeSet::new();
@@ -66,40 +72,122 @@ pub fn list_dir(dir: String) -> Vec<String> {
     results.into_iter().collect()
 }
 
+/// One page of `list_all_objects`, along with the total number of objects. The listing is cheap
+/// here, so nothing is cached.
+pub fn list_objects_page(dir: String, offset: usize, limit: usize) -> (Vec<String>, usize) {
+    let names = list_all_objects(dir);
+    let total = names.len();
+    (names.into_iter().skip(offset).take(limit).collect(), total)
+}
+
+/// Files can't be changed on web, so this does nothing.
+pub fn repair_directory(dir: String, _dry_run: bool, _add_checksums: bool) -> Vec<RepairAction> {
+    warn!("Can't repair {} on web", dir);
+    Vec::new()
+}
+
+/// Only the files directly in `dir` are checked, and only bundled files can be read.
+pub fn scan_integrity(dir: String) -> Vec<(String, IntegrityStatus)> {
+    list_dir(dir)
+        .into_iter()
+        .filter(|path| path.ends_with(".bin") || path.ends_with(".json"))
+        .map(|path| {
+            let status = check_integrity(&path);
+            (path, status)
+        })
+        .collect()
+}
+
 pub fn slurp_file(path: &str) -> Result<Vec<u8>, String> {
+    try_slurp_file(path).map_err(|err| err.to_string())
+}
+
+pub fn try_slurp_file(path: &str) -> Result<Vec<u8>, ReadError> {
     if let Some(raw) = SYSTEM_DATA.get_file(path.trim_start_matches("../data/system/")) {
         Ok(raw.contents().to_vec())
     } else if path.starts_with(&path_player("")) {
-        let window = web_sys::window().ok_or("no window?".to_string())?;
+        let window = web_sys::window().ok_or(web_error("no window?".to_string()))?;
         let storage = window
             .local_storage()
             .map_err(|err| {
-                err.as_string()
-                    .unwrap_or("local_storage failed".to_string())
+                web_error(
+                    err.as_string()
+                        .unwrap_or("local_storage failed".to_string()),
+                )
             })?
-            .ok_or("no local_storage?".to_string())?;
+            .ok_or(web_error("no local_storage?".to_string()))?;
         let string = storage
             .get_item(path)
-            .map_err(|err| err.as_string().unwrap_or("get_item failed".to_string()))?
-            .ok_or(format!("{} missing from local storage", path))?;
+            .map_err(|err| web_error(err.as_string().unwrap_or("get_item failed".to_string())))?
+            .ok_or(ReadError::NotFound)?;
         Ok(string.into_bytes())
     } else {
-        Err(format!("Can't slurp_file {}, it doesn't exist", path))
+        Err(ReadError::NotFound)
+    }
+}
+
+fn web_error(msg: String) -> ReadError {
+    ReadError::Io(std::io::Error::new(std::io::ErrorKind::Other, msg))
+}
+
+pub fn try_read_binary<T: DeserializeOwned>(
+    path: String,
+    timer: &mut Timer,
+) -> Result<T, ReadError> {
+    try_read_binary_with_limit(path, BinaryLimit::default(), timer)
+}
+
+pub fn try_read_binary_with_limit<T: DeserializeOwned>(
+    path: String,
+    limit: BinaryLimit,
+    _: &mut Timer,
+) -> Result<T, ReadError> {
+    if let Some(raw) = SYSTEM_DATA.get_file(path.trim_start_matches("../data/system/")) {
+        let limit = limit.bytes(raw.contents().len() as u64);
+        let (format, _, body) = strip_binary_header::<T, _>(&path, raw.contents())?;
+        deserialize_limited(&path, format, body, limit)
+    } else {
+        Err(ReadError::NotFound)
+    }
+}
+
+pub fn try_read_compressed_binary<T: DeserializeOwned>(
+    path: String,
+    limit: BinaryLimit,
+    _: &mut Timer,
+) -> Result<T, ReadError> {
+    if let Some(raw) = SYSTEM_DATA.get_file(path.trim_start_matches("../data/system/")) {
+        let bytes = decompress(&path, raw.contents())?;
+        let limit = limit.bytes(bytes.len() as u64);
+        let (format, _, body) = strip_binary_header::<T, _>(&path, &bytes[..])?;
+        deserialize_limited(&path, format, body, limit)
+    } else {
+        Err(ReadError::NotFound)
     }
 }
 
-pub fn maybe_read_binary<T: DeserializeOwned>(path: String, _: &mut Timer) -> Result<T, String> {
+pub fn read_binary_verified<T: DeserializeOwned>(
+    path: String,
+    _: &mut Timer,
+) -> Result<T, ReadError> {
     if let Some(raw) = SYSTEM_DATA.get_file(path.trim_start_matches("../data/system/")) {
-        bincode::deserialize(raw.contents()).map_err(|x| x.to_string())
+        let limit = BinaryLimit::default().bytes(raw.contents().len() as u64);
+        let mut reader = HashingReader::new(raw.contents());
+        let (format, _, body) = strip_binary_header::<T, _>(&path, &mut reader)?;
+        let obj = deserialize_limited(&path, format, body, limit)?;
+        check_md5_sidecar(&path, reader.finish()?)?;
+        Ok(obj)
     } else {
-        Err(format!(
-            "Can't maybe_read_binary {}, it doesn't exist",
-            path
-        ))
+        Err(ReadError::NotFound)
     }
 }
 
+/// Writes compact JSON. Use `write_json_with` for something people will read or diff.
 pub fn write_json<T: Serialize>(path: String, obj: &T) {
+    write_json_with(path, obj, JsonFormat::Compact);
+}
+
+pub fn write_json_with<T: Serialize>(path: String, obj: &T, format: JsonFormat) {
     // Only save for data/player, for now
     if !path.starts_with(&path_player("")) {
         warn!("Not saving {}", path);
@@ -108,7 +196,7 @@ pub fn write_json<T: Serialize>(path: String, obj: &T) {
 
     let window = web_sys::window().unwrap();
     let storage = window.local_storage().unwrap().unwrap();
-    storage.set_item(&path, &to_json(obj)).unwrap();
+    storage.set_item(&path, &format.stringify(obj)).unwrap();
 }
 
 pub fn write_binary<T: Serialize>(path: String, _obj: &T) {
@@ -116,7 +204,140 @@ pub fn write_binary<T: Serialize>(path: String, _obj: &T) {
     warn!("Not saving {}", path);
 }
 
+/// Saving isn't supported on web yet; this is just `write_binary`.
+pub fn write_binary_with<T: Serialize>(path: String, obj: &T, _: BinaryFormat) {
+    write_binary(path, obj);
+}
+
+/// Saving isn't supported on web yet; this is just `write_binary`.
+pub fn write_binary_compressed<T: Serialize>(path: String, obj: &T, _: Compression) {
+    write_binary(path, obj);
+}
+
+/// There's no fsync on web; this is just `write_binary`.
+pub fn write_binary_durable<T: Serialize>(path: String, obj: &T) {
+    write_binary(path, obj);
+}
+
+/// Saving isn't supported on web yet; this just returns the hash the blob would have.
+pub fn write_binary_cas<T: Serialize>(dir: String, obj: &T) -> String {
+    match encode_cas_blob(obj) {
+        Ok((hash, _)) => {
+            warn!("Not saving {}", cas_path(&dir, &hash));
+            hash
+        }
+        Err(err) => panic!("Can't write_binary_cas({}): {}", dir, err),
+    }
+}
+
+/// Files can't be deleted on web, so this does nothing.
+pub fn gc_cas(dir: String, _: &BTreeSet<String>) -> usize {
+    warn!("Can't clean up {} on web", dir);
+    0
+}
+
+/// Files can't be inspected on web.
+pub fn file_mtime(_: &str) -> Option<std::time::SystemTime> {
+    None
+}
+
+/// Files can't be inspected on web.
+pub fn file_version(_: &str) -> Option<FileVersion> {
+    None
+}
+
+/// Saving isn't supported on web yet, so this always fails.
+pub fn write_binary_if_not_newer<T: Serialize>(
+    path: String,
+    _: &T,
+    _: Option<FileVersion>,
+) -> Result<FileVersion, WriteError> {
+    Err(WriteError::Failed(format!("Can't save {} on web", path)))
+}
+
+/// There are no threads on web, so this saves right away on every change, ignoring the interval.
+pub struct Autosaver {
+    path: String,
+    last_saved: std::cell::Cell<Option<instant::Instant>>,
+}
+
+impl Autosaver {
+    pub fn new(path: String, _: std::time::Duration) -> Autosaver {
+        Autosaver {
+            path,
+            last_saved: std::cell::Cell::new(None),
+        }
+    }
+
+    pub fn mark_dirty<T: Serialize, F: 'static + Send + FnOnce() -> T>(&self, serialize: F) {
+        write_binary(self.path.clone(), &serialize());
+        self.last_saved.set(Some(instant::Instant::now()));
+    }
+
+    pub fn last_saved(&self) -> Option<instant::Instant> {
+        self.last_saved.get()
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        None
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// There's no backup on web; this is just `write_binary`.
+pub fn write_binary_with_backup<T: Serialize>(path: String, obj: &T) {
+    write_binary(path, obj);
+}
+
+/// There's no backup on web; this is just `write_json`.
+pub fn write_json_with_backup<T: Serialize>(path: String, obj: &T) {
+    write_json(path, obj);
+}
+
+/// Saving isn't supported on web yet, so this just warns about each file.
+pub struct SaveTransaction {}
+
+impl SaveTransaction {
+    pub fn new() -> SaveTransaction {
+        SaveTransaction {}
+    }
+
+    pub fn write_binary<T: Serialize>(
+        &mut self,
+        path: String,
+        _obj: &T,
+    ) -> Result<(), Box<dyn Error>> {
+        warn!("Not saving {}", path);
+        Ok(())
+    }
+
+    pub fn write_json<T: Serialize>(
+        &mut self,
+        path: String,
+        _obj: &T,
+    ) -> Result<(), Box<dyn Error>> {
+        warn!("Not saving {}", path);
+        Ok(())
+    }
+
+    pub fn commit(self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+pub fn write_csv<T: Serialize>(path: String, _rows: &[T]) {
+    // TODO
+    warn!("Not saving {}", path);
+}
+
 pub fn delete_file<I: Into<String>>(path: I) {
     // TODO
     warn!("Not deleting {}", path.into());
 }
+
+pub fn trash_file<I: Into<String>>(path: I) {
+    delete_file(path);
+}
@@ -1,4 +1,4 @@
-use crate::MultiMap;
+use crate::{binary_header, strip_binary_header, BinaryFormat, MultiMap};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ord;
@@ -15,6 +15,35 @@ pub fn to_json_terse<T: Serialize>(obj: &T) -> String {
     serde_json::to_string(obj).unwrap()
 }
 
+/// How `write_json_with` lays out JSON.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JsonFormat {
+    Compact,
+    Pretty,
+}
+
+impl JsonFormat {
+    /// Stringifies an object using this format.
+    pub fn stringify<T: Serialize>(self, obj: &T) -> String {
+        match self {
+            JsonFormat::Compact => to_json_terse(obj),
+            JsonFormat::Pretty => to_json(obj),
+        }
+    }
+
+    /// Serializes an object straight into a writer, without building the whole string in memory.
+    pub fn write_to<W: std::io::Write, T: Serialize>(
+        self,
+        writer: W,
+        obj: &T,
+    ) -> Result<(), serde_json::Error> {
+        match self {
+            JsonFormat::Compact => serde_json::to_writer(writer, obj),
+            JsonFormat::Pretty => serde_json::to_writer_pretty(writer, obj),
+        }
+    }
+}
+
 /// Deserializes an object from a JSON string.
 pub fn from_json<T: DeserializeOwned>(raw: &Vec<u8>) -> Result<T, String> {
     serde_json::from_slice(raw).map_err(|x| x.to_string())
@@ -25,14 +54,35 @@ pub fn from_json_reader<R: std::io::Read, T: DeserializeOwned>(reader: R) -> Res
     serde_json::from_reader(reader).map_err(|x| x.to_string())
 }
 
+/// Serializes an object to the bincode format in memory, including the same header that
+/// `write_binary` puts at the start of files.
+pub fn to_binary_bytes<T: Serialize>(obj: &T) -> Result<Vec<u8>, String> {
+    let mut bytes = binary_header::<T>(BinaryFormat::Bincode);
+    bincode::serialize_into(&mut bytes, obj).map_err(|x| x.to_string())?;
+    Ok(bytes)
+}
+
+/// Deserializes an object from bytes produced by `to_binary_bytes`.
+pub fn from_binary_bytes<T: DeserializeOwned>(raw: &[u8]) -> Result<T, String> {
+    from_binary_reader(raw)
+}
+
 /// Deserializes an object from the bincode format.
 pub fn from_binary<T: DeserializeOwned>(raw: &Vec<u8>) -> Result<T, String> {
-    bincode::deserialize(raw).map_err(|x| x.to_string())
+    from_binary_reader(&raw[..])
 }
 
-/// Deserializes an object from the bincode format, from a reader.
+/// Deserializes an object from the bincode format, from a reader. Data written with
+/// `BinaryFormat::MessagePack` works too.
 pub fn from_binary_reader<R: std::io::Read, T: DeserializeOwned>(reader: R) -> Result<T, String> {
-    bincode::deserialize_from(reader).map_err(|x| x.to_string())
+    let (format, _, reader) =
+        strip_binary_header::<T, _>("binary data", reader).map_err(|x| x.to_string())?;
+    match format {
+        BinaryFormat::Bincode => bincode::deserialize_from(reader).map_err(|x| x.to_string()),
+        BinaryFormat::MessagePack => {
+            rmp_serde::decode::from_read(reader).map_err(|x| x.to_string())
+        }
+    }
 }
 
 /// The number of bytes for an object serialized to bincode.
@@ -141,3 +191,22 @@ pub fn deserialize_usize<'de, D: Deserializer<'de>>(d: D) -> Result<usize, D::Er
     let x = <u32>::deserialize(d)?;
     Ok(x as usize)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_bytes_round_trip() {
+        let obj = vec![("a".to_string(), 1_usize), ("b".to_string(), 2)];
+        let bytes = to_binary_bytes(&obj).unwrap();
+        assert_eq!(
+            from_binary_bytes::<Vec<(String, usize)>>(&bytes).unwrap(),
+            obj
+        );
+        // bincode alone would read this as ["a", "\x01"]
+        assert!(from_binary_bytes::<Vec<String>>(&bytes)
+            .unwrap_err()
+            .contains("expected Vec<String>, file contains Vec<(String, usize)>"));
+    }
+}
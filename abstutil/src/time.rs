@@ -117,6 +117,17 @@ pub struct Timer<'a> {
     pub(crate) errors: Vec<String>,
 
     sink: Option<Box<dyn TimerSink + 'a>>,
+
+    created_at: Instant,
+    // Every finished span, iteration, and file read, for save_chrome_trace
+    trace: Vec<TraceEvent>,
+}
+
+struct TraceEvent {
+    name: String,
+    // Microseconds since the Timer was created
+    start: f64,
+    duration: f64,
 }
 
 struct TimerSpan {
@@ -144,6 +155,8 @@ impl<'a> Timer<'a> {
             warnings: Vec::new(),
             errors: Vec::new(),
             sink: None,
+            created_at: Instant::now(),
+            trace: Vec::new(),
         };
         t.start(name);
         t
@@ -199,9 +212,28 @@ impl<'a> Timer<'a> {
         self.errors.push(line);
     }
 
-    /// Used to end the scope of a timer early.
+    /// Used to end the scope of a timer early. Like dropping the timer, this complains about
+    /// anything started and not finished.
     pub fn done(self) {}
 
+    /// Describes everything started and not finished, besides the outermost span.
+    fn unbalanced(&self) -> Vec<String> {
+        self.stack
+            .iter()
+            .skip(1)
+            .map(|entry| match entry {
+                StackEntry::TimerSpan(s) => format!("start(\"{}\") without stop()", s.name),
+                StackEntry::Progress(p) => format!(
+                    "start_iter(\"{}\") stopped at {}/{}",
+                    p.label,
+                    prettyprint_usize(p.processed_items),
+                    prettyprint_usize(p.total_items)
+                ),
+                StackEntry::File(f) => format!("{} wasn't read to the end", f.path),
+            })
+            .collect()
+    }
+
     pub fn start<S: Into<String>>(&mut self, raw_name: S) {
         if self.outermost_name == "throwaway" {
             return;
@@ -230,6 +262,7 @@ impl<'a> Timer<'a> {
         };
         assert_eq!(span.name, name);
         let elapsed = elapsed_seconds(span.started_at);
+        self.record_trace(name.clone(), span.started_at, elapsed);
         let line = format!("{} took {}", name, prettyprint_time(elapsed));
 
         let padding = "  ".repeat(self.stack.len());
@@ -306,7 +339,9 @@ impl<'a> Timer<'a> {
                 panic!("Can't next() while a TimerSpan is top of the stack");
             };
         if let Some((elapsed, result)) = maybe_result {
-            self.stack.pop();
+            if let Some(StackEntry::Progress(p)) = self.stack.pop() {
+                self.record_trace(p.label, p.started_at, elapsed);
+            }
             self.add_result(elapsed, result);
         }
     }
@@ -320,7 +355,9 @@ impl<'a> Timer<'a> {
         } else {
             panic!("Can't cancel_iter_early() while a TimerSpan is top of the stack");
         };
-        self.stack.pop();
+        if let Some(StackEntry::Progress(p)) = self.stack.pop() {
+            self.record_trace(format!("{} (cancelled)", p.label), p.started_at, elapsed);
+        }
         self.add_result(elapsed, format!("cancelled early"));
     }
 
@@ -399,13 +436,58 @@ impl<'a> Timer<'a> {
         }
     }
 
+    fn record_trace(&mut self, name: String, started_at: Instant, elapsed: f64) {
+        self.trace.push(TraceEvent {
+            name,
+            start: started_at.duration_since(self.created_at).as_micros() as f64,
+            duration: elapsed * 1e6,
+        });
+    }
+
+    /// Everything timed so far, in the Chrome Trace Event format. Spans that haven't stopped yet
+    /// (including the outermost one) are cut off at the current time.
+    pub fn chrome_trace(&self) -> serde_json::Value {
+        let mut events: Vec<serde_json::Value> = self
+            .trace
+            .iter()
+            .map(|ev| trace_event(&ev.name, ev.start, ev.duration))
+            .collect();
+        for entry in &self.stack {
+            if let StackEntry::TimerSpan(span) = entry {
+                let start = span.started_at.duration_since(self.created_at).as_micros() as f64;
+                events.push(trace_event(
+                    &span.name,
+                    start,
+                    elapsed_seconds(span.started_at) * 1e6,
+                ));
+            }
+        }
+        serde_json::json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+        })
+    }
+
+    /// Writes `chrome_trace`, to open in chrome://tracing or https://ui.perfetto.dev and see a
+    /// flamegraph of where the time went.
+    pub fn save_chrome_trace(&self, path: String) {
+        crate::write_json(path, &self.chrome_trace());
+    }
+
     /// Then the caller passes this in as a reader
-    pub(crate) fn read_file(&mut self, path: &str) -> Result<(), String> {
-        self.stack.push(StackEntry::File(
-            TimedFileReader::new(path).map_err(|err| err.to_string())?,
-        ));
+    pub(crate) fn read_file(&mut self, path: &str) -> Result<(), Error> {
+        self.stack
+            .push(StackEntry::File(TimedFileReader::new(path)?));
         Ok(())
     }
+
+    /// Drops the file pushed by `read_file` if it wasn't read to the end, so a failed read doesn't
+    /// leave the caller's spans unbalanced.
+    pub(crate) fn abandon_file(&mut self, path: &str) {
+        if matches!(self.stack.last(), Some(StackEntry::File(f)) if f.path == path) {
+            self.stack.pop();
+        }
+    }
 }
 
 impl<'a> std::ops::Drop for Timer<'a> {
@@ -416,26 +498,26 @@ impl<'a> std::ops::Drop for Timer<'a> {
 
         let stop_name = self.outermost_name.clone();
 
-        // If we're in the middle of unwinding a panic, don't further blow up.
-        match self.stack.last() {
-            Some(StackEntry::TimerSpan(ref s)) => {
-                if s.name != stop_name {
-                    println!("dropping Timer during {}, due to panic?", s.name);
-                    return;
-                }
-            }
-            Some(StackEntry::File(ref r)) => {
-                println!("dropping Timer while reading {}, due to panic?", r.path);
-                return;
-            }
-            Some(StackEntry::Progress(ref p)) => {
-                println!(
-                    "dropping Timer while doing progress {}, due to panic?",
-                    p.label
-                );
-                return;
+        let unbalanced = self.unbalanced();
+        if !unbalanced.is_empty() {
+            let msg = format!(
+                "Timer {} dropped with unbalanced operations: {}",
+                stop_name,
+                unbalanced.join("; ")
+            );
+            // Decoding a file can fail partway and leave it half-read, which callers may handle.
+            // Anything else left open is an instrumentation bug. If we're in the middle of
+            // unwinding a panic, don't further blow up.
+            let is_bug = self
+                .stack
+                .iter()
+                .skip(1)
+                .any(|entry| !matches!(entry, StackEntry::File(_)));
+            if is_bug && cfg!(debug_assertions) && !std::thread::panicking() {
+                panic!("{}", msg);
             }
-            None => unreachable!(),
+            println!("{}", msg);
+            return;
         }
 
         self.stop(&stop_name);
@@ -485,6 +567,18 @@ impl<'a> std::ops::Drop for Timer<'a> {
     }
 }
 
+// A "complete" event. Nesting comes from the timestamps, since everything's on one thread.
+fn trace_event(name: &str, start: f64, duration: f64) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "ph": "X",
+        "ts": start,
+        "dur": duration,
+        "pid": 1,
+        "tid": 1,
+    })
+}
+
 pub fn prettyprint_time(seconds: f64) -> String {
     format!("{:.4}s", seconds)
 }
@@ -557,6 +651,8 @@ impl<'a> Read for Timer<'a> {
 
         if file.processed_bytes == file.total_bytes {
             let elapsed = elapsed_seconds(file.started_at);
+            let trace_name = format!("Read {}", file.path);
+            let started_at = file.started_at;
             let line = format!(
                 "Read {} ({})... {}",
                 file.path,
@@ -575,6 +671,7 @@ impl<'a> Read for Timer<'a> {
                 }
             }
             self.stack.pop();
+            self.record_trace(trace_name, started_at, elapsed);
             self.add_result(elapsed, line);
         } else if file.last_printed_at.is_none()
             || elapsed_seconds(file.last_printed_at.unwrap()) >= PROGRESS_FREQUENCY_SECONDS
@@ -607,3 +704,58 @@ impl<'a> Read for Timer<'a> {
         Ok(bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chrome_traces_nest() {
+        let mut timer = Timer::new("test");
+        timer.start("outer");
+        timer.start_iter("items", 2);
+        timer.next();
+        timer.next();
+        timer.stop("outer");
+
+        let trace = timer.chrome_trace();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let names: Vec<&str> = events
+            .iter()
+            .map(|ev| ev["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["items", "outer", "test"]);
+        let end = |ev: &serde_json::Value| ev["ts"].as_f64().unwrap() + ev["dur"].as_f64().unwrap();
+        let (items, outer) = (&events[0], &events[1]);
+        assert!(items["ts"].as_f64().unwrap() >= outer["ts"].as_f64().unwrap());
+        assert!(end(items) <= end(outer) + 1.0);
+    }
+
+    #[test]
+    fn reports_unbalanced_operations() {
+        let mut timer = Timer::new("test");
+        timer.start("outer");
+        timer.start_iter("items", 3);
+        timer.next();
+        assert_eq!(
+            timer.unbalanced(),
+            vec![
+                "start(\"outer\") without stop()".to_string(),
+                "start_iter(\"items\") stopped at 1/3".to_string()
+            ]
+        );
+
+        timer.next();
+        timer.next();
+        timer.stop("outer");
+        assert!(timer.unbalanced().is_empty());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "start(\"forgotten\") without stop()")]
+    fn dropping_with_an_open_span_panics() {
+        let mut timer = Timer::new("test");
+        timer.start("forgotten");
+    }
+}
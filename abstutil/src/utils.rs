@@ -45,6 +45,18 @@ pub fn prettyprint_usize(x: usize) -> String {
     result
 }
 
+/// Parses numbers from messy input, like attributes in GIS datasets. Whitespace and thousands
+/// separators ("1,234") are ignored. Returns None for anything that isn't a finite number.
+pub fn parse_loose_f64(raw: &str) -> Option<f64> {
+    let cleaned = raw.trim().replace(',', "");
+    let x = cleaned.parse::<f64>().ok()?;
+    if x.is_finite() {
+        Some(x)
+    } else {
+        None
+    }
+}
+
 pub fn basename<I: Into<String>>(path: I) -> String {
     std::path::Path::new(&path.into())
         .file_stem()
@@ -57,3 +69,88 @@ pub fn basename<I: Into<String>>(path: I) -> String {
 pub fn parent_path(path: &str) -> String {
     format!("{}", std::path::Path::new(path).parent().unwrap().display())
 }
+
+/// Resolves `.` and `..` segments, collapses repeated slashes, drops any trailing slash, and uses
+/// `/` as the separator, so two spellings of the same path compare equal. Unlike
+/// `std::fs::canonicalize`, the file doesn't need to exist and symlinks aren't followed. `..` past
+/// the start of a relative path is kept.
+pub fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let absolute = path.starts_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => match parts.last() {
+                Some(last) if *last != ".." && !last.ends_with(':') => {
+                    parts.pop();
+                }
+                // Can't go above the root or a Windows drive
+                Some(last) if last.ends_with(':') => {}
+                None if absolute => {}
+                _ => {
+                    parts.push(part);
+                }
+            },
+            _ => {
+                parts.push(part);
+            }
+        }
+    }
+    let joined = parts.join("/");
+    if absolute {
+        format!("/{}", joined)
+    } else if joined.is_empty() {
+        ".".to_string()
+    } else {
+        joined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_relative_paths() {
+        assert_eq!(normalize_path("./data/foo.bin"), "data/foo.bin");
+        assert_eq!(normalize_path("data/foo.bin"), "data/foo.bin");
+        assert_eq!(normalize_path("data/system/../foo.bin"), "data/foo.bin");
+        assert_eq!(normalize_path("../data/./foo.bin"), "../data/foo.bin");
+        assert_eq!(normalize_path("data/../../foo.bin"), "../foo.bin");
+        assert_eq!(normalize_path("data/.."), ".");
+    }
+
+    #[test]
+    fn normalize_redundant_and_trailing_slashes() {
+        assert_eq!(
+            normalize_path("data//system///foo.bin"),
+            "data/system/foo.bin"
+        );
+        assert_eq!(normalize_path("data/system/"), "data/system");
+        assert_eq!(normalize_path("/"), "/");
+        assert_eq!(normalize_path("/tmp/../../x"), "/x");
+    }
+
+    #[test]
+    fn normalize_windows_separators() {
+        assert_eq!(
+            normalize_path("data\\system\\foo.bin"),
+            "data/system/foo.bin"
+        );
+        assert_eq!(normalize_path("C:\\data\\..\\..\\foo.bin"), "C:/foo.bin");
+    }
+
+    #[test]
+    fn messy_numbers() {
+        assert_eq!(Some(12.0), parse_loose_f64("12"));
+        assert_eq!(Some(12.0), parse_loose_f64(" 12 "));
+        assert_eq!(Some(1234.5), parse_loose_f64("1,234.5"));
+        assert_eq!(Some(-3.0), parse_loose_f64("-3"));
+        assert_eq!(None, parse_loose_f64(""));
+        assert_eq!(None, parse_loose_f64("N/A"));
+        assert_eq!(None, parse_loose_f64("12 meters"));
+        assert_eq!(None, parse_loose_f64("NaN"));
+        assert_eq!(None, parse_loose_f64("inf"));
+    }
+}
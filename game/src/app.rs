@@ -831,9 +831,10 @@ impl SharedAppState for App {
         if self.primary.map.get_edits().commands.is_empty() {
             println!("No edits");
         } else {
-            abstutil::write_json(
+            abstutil::write_json_with(
                 "edits_during_crash.json".to_string(),
                 &self.primary.map.get_edits().to_permanent(&self.primary.map),
+                abstutil::JsonFormat::Pretty,
             );
             println!("Please include edits_during_crash.json in your bug report.");
         }
@@ -460,7 +460,7 @@ impl State<App> for SimpleStateWrapper {
         }
         match self.panel.event(ctx) {
             Outcome::Clicked(action) => self.inner.on_click(ctx, app, &action, &self.panel),
-            Outcome::Changed => self
+            Outcome::Changed | Outcome::Reordered(_, _) => self
                 .inner
                 .panel_changed(ctx, app, &self.panel)
                 .unwrap_or_else(|| self.inner.other_event(ctx, app)),
@@ -19,7 +19,7 @@ pub fn export(roads: Vec<RoadID>, map: &Map) -> String {
         features: roads.into_iter().map(|r| road(r, map)).collect(),
         foreign_members: None,
     });
-    abstutil::write_json(path.clone(), &geojson);
+    abstutil::write_json_with(path.clone(), &geojson, abstutil::JsonFormat::Pretty);
     path
 }
 
@@ -1,58 +1,261 @@
 // TODO Rename -- this is for KML, CSV, GeoJSON
 
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use aabb_quadtree::QuadTree;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 use abstutil::{prettyprint_usize, Parallelism, Timer};
-use geom::{Circle, Distance, PolyLine, Polygon, Pt2D, Ring};
+use geom::{
+    Bounds, Circle, Distance, FindClosest, LonLat, Percent, PolyLine, Polygon, Pt2D, Ring, Time,
+};
 use kml::{ExtraShape, ExtraShapes};
 use map_gui::colors::ColorScheme;
-use map_gui::tools::{ChooseSomething, PopupMsg};
+use map_gui::tools::{
+    grey_out_map, make_heatmap, ChooseSomething, ColorLegend, HeatmapOptions, PopupMsg,
+};
 use map_model::BuildingID;
+use sim::TripEndpoint;
 use widgetry::{
-    lctrl, Btn, Choice, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key,
-    Line, Outcome, Panel, State, Text, TextExt, VerticalAlignment, Widget,
+    lctrl, AccessRole, Btn, Canvas, Checkbox, Choice, Color, DrawBaselayer, Drawable, EventCtx,
+    GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, ScreenPt, ScreenRectangle,
+    Slider, Spinner, State, Text, TextExt, UpdateType, VerticalAlignment, Widget,
 };
 
 use crate::app::{App, Transition};
+use crate::common::Warping;
+use crate::devtools::story::Lasso;
+use crate::sandbox::dashboards::block_shapes;
 
 pub struct ViewKML {
     panel: Panel,
+    path: Option<String>,
+    dataset_name: String,
     objects: Vec<Object>,
+    // Before sampling; more than objects.len() for huge datasets
+    total_objects: usize,
     draw: Drawable,
 
     selected: Option<usize>,
     quadtree: QuadTree<usize>,
     draw_query: Drawable,
+    // What the filter and regex are looking for, to point out in the tooltip
+    search: AttributeSearch,
+    draw_outside: Drawable,
+
+    lasso: Option<Lasso>,
+    selection: BTreeSet<usize>,
+    draw_selection: Drawable,
+
+    timeline: Option<Timeline>,
+    heatmap: Option<Heatmap>,
+    block_counts: Option<BlockCounts>,
+    // "None" when no attribute is summarized
+    stats_attribute: String,
+    // "None" when every object is drawn at the same opacity
+    opacity_attribute: String,
+    // Percent, for objects that don't have a number for opacity_attribute
+    default_opacity: isize,
+
+    // Objects whose attributes or positions were changed by hand
+    edited: BTreeSet<usize>,
+    draw_edited: Drawable,
+    drag: Option<Drag>,
+    // Meters to grow (or shrink, if negative) the selected objects by. 0 is off.
+    buffer_distance: isize,
+    // What's on disk, so unchanged preferences aren't written again
+    saved_preferences: Preferences,
+}
+
+/// Which attributes the "filter" and "regex" boxes match.
+#[derive(Default)]
+struct AttributeSearch {
+    // Matched against "key=value"
+    filter: Option<String>,
+    regex: Option<(String, Regex)>,
+}
+
+impl AttributeSearch {
+    fn matches(&self, key: &str, value: &str) -> bool {
+        if let Some(ref filter) = self.filter {
+            if format!("{}={}", key, value).contains(filter) {
+                return true;
+            }
+        }
+        if let Some((ref attribute, ref re)) = self.regex {
+            if attribute == key && re.is_match(value) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn is_active(&self) -> bool {
+        self.filter.is_some() || self.regex.is_some()
+    }
+}
+
+/// An object being moved by hand. Nothing changes until the mouse is released.
+struct Drag {
+    idx: usize,
+    // Where the cursor was when the drag started
+    start: Pt2D,
+    // None until the mouse moves; releasing before then is just a click
+    current: Option<Pt2D>,
+    // Faintly shows where the object was
+    draw_original: Drawable,
+}
+
+/// The density of point objects.
+struct Heatmap {
+    opts: HeatmapOptions,
+    // The camera zoom the resolution was last adapted to
+    zoom: f64,
+    // Once the resolution is picked by hand, zooming leaves it alone
+    manual_resolution: bool,
+    draw: Drawable,
+}
+
+/// How many point objects fall inside each block of buildings.
+struct BlockCounts {
+    // Indexed by block
+    blocks: Vec<(Polygon, usize)>,
+    draw: Drawable,
+}
+
+/// One line of the file written by "export block counts"
+#[derive(Serialize)]
+struct BlockCountRow {
+    block: usize,
+    longitude: f64,
+    latitude: f64,
+    count: usize,
+}
+
+/// Reveals objects over time, ordered by some timestamp attribute.
+struct Timeline {
+    // (time, object index), sorted by time. Objects whose attribute doesn't parse aren't here;
+    // they're drawn statically instead.
+    events: Vec<(f64, usize)>,
+    current_pct: f64,
+    playing: bool,
+    draw: Drawable,
+}
+
+/// Remembered per dataset, so reopening the same file restores the attributes and camera last
+/// used.
+#[derive(Default, PartialEq, Serialize, Deserialize)]
+struct Preferences {
+    query: String,
+    filter: String,
+    regex_attribute: String,
+    regex: String,
+    time_attribute: String,
+    // (center, zoom)
+    camera: Option<(Pt2D, f64)>,
+}
+
+impl Preferences {
+    fn path(dataset_name: &str) -> String {
+        abstutil::path_player(format!("kml_viewer/{}.json", dataset_name))
+    }
 }
 
 struct Object {
     polygon: Polygon,
     color: Color,
     attribs: BTreeMap<String, String>,
+    // Only for objects that are a single point
+    point: Option<Pt2D>,
+    geometry: GeometryType,
+    // The original points, to save edits back to a file. Dragging moves these too.
+    gps_points: Vec<LonLat>,
+    // How far the object has been dragged in total, in map-space meters
+    moved_by: Option<(f64, f64)>,
 
     osm_bldg: Option<BuildingID>,
 }
 
+/// The shape of an object in the source file
+#[derive(Clone, Copy, PartialEq)]
+enum GeometryType {
+    Point,
+    LineString,
+    Polygon,
+}
+
 const RADIUS: Distance = Distance::const_meters(5.0);
 const THICKNESS: Distance = Distance::const_meters(2.0);
+// How long it takes to play through the entire timeline
+const PLAYBACK_SECONDS: f64 = 30.0;
+// Buffering works edge by edge, so it's too slow for big selections
+const MAX_BUFFERED_OBJECTS: usize = 200;
+
+// While searching, the tooltip lists at most this many attributes; the rest don't explain the match
+const MAX_TOOLTIP_ATTRIBUTES: usize = 10;
+
+// Drawing and querying more objects than this makes the viewer crawl, so bigger datasets are
+// sampled
+const MAX_OBJECTS: usize = 100_000;
+// The values of an opacity attribute are scaled to this range. Without one, everything is drawn
+// at the upper end.
+const OPACITY_RANGE: (f32, f32) = (0.1, 0.8);
+const DEFAULT_MISSING_OPACITY: isize = 20;
+// Only objects at most this many meters across can be dragged, so big polygons don't get moved by
+// accident. Points always can.
+const DEFAULT_MAX_DRAG_SIZE: isize = 50;
+// Objects without a matching building only become trip endpoints if something is this close
+const MAX_SNAP_DISTANCE: Distance = Distance::const_meters(100.0);
 
 impl ViewKML {
     pub fn new(ctx: &mut EventCtx, app: &App, path: Option<String>) -> Box<dyn State<App>> {
         ctx.loading_screen("load kml", |ctx, mut timer| {
             // Enable to write a smaller .bin only with the shapes matching the bounds.
             let dump_clipped_shapes = false;
-            let (dataset_name, objects) = load_objects(app, path, dump_clipped_shapes, &mut timer);
-
-            let mut batch = GeomBatch::new();
-            let mut quadtree = QuadTree::default(app.primary.map.get_bounds().as_bbox());
-            timer.start_iter("render shapes", objects.len());
-            for (idx, obj) in objects.iter().enumerate() {
-                timer.next();
-                quadtree.insert_with_box(idx, obj.polygon.get_bounds().as_bbox());
-                batch.push(obj.color, obj.polygon.clone());
+            let (dataset_name, mut objects, outside) =
+                load_objects(app, path.clone(), dump_clipped_shapes, &mut timer);
+            let total_objects = objects.len();
+            let geometry_summary = describe_geometry_types(&objects);
+            let sample_warning = if total_objects > MAX_OBJECTS {
+                objects = sample_evenly(objects, MAX_OBJECTS);
+                let warning = format!(
+                    "Dataset has {} features; showing a {} sample",
+                    prettyprint_usize(total_objects),
+                    prettyprint_usize(objects.len())
+                );
+                timer.note(warning.clone());
+                Line(warning).fg(Color::RED).draw(ctx)
+            } else {
+                Widget::nothing()
+            };
+
+            let (draw, quadtree) = render_objects(ctx, app, &objects, &mut timer);
+
+            let mut outside_batch = GeomBatch::new();
+            for pt in &outside {
+                outside_batch.push(
+                    Color::PURPLE.alpha(0.8),
+                    Circle::new(*pt, RADIUS * 3.0).to_polygon(),
+                );
             }
+            let outside_summary = if outside.is_empty() {
+                Widget::nothing()
+            } else {
+                Widget::col(vec![
+                    format!(
+                        "{} of {} objects fell outside the map bounds",
+                        prettyprint_usize(outside.len()),
+                        prettyprint_usize(total_objects + outside.len())
+                    )
+                    .draw_text(ctx),
+                    Checkbox::checkbox(ctx, "show outside objects at the map edge", None, false),
+                ])
+            };
+
+            let prefs: Preferences =
+                abstutil::read_json_lenient(Preferences::path(&dataset_name), &mut timer)
+                    .unwrap_or_default();
 
             let mut choices = vec![Choice::string("None")];
             if dataset_name == "parcels" {
@@ -64,9 +267,35 @@ impl ViewKML {
                 choices.push(Choice::string("parcels with >1 households"));
                 choices.push(Choice::string("parcels with parking"));
             }
+            let query = if choices.iter().any(|c| c.data == prefs.query) {
+                prefs.query.clone()
+            } else {
+                "None".to_string()
+            };
+            let mut regex_choices = vec![Choice::string("None")];
+            regex_choices.extend(Choice::strings(
+                all_attributes(&objects).into_iter().collect(),
+            ));
+            let regex_attribute = if regex_choices
+                .iter()
+                .any(|c| c.data == prefs.regex_attribute)
+            {
+                prefs.regex_attribute.clone()
+            } else {
+                "None".to_string()
+            };
+            let restore_query = query != "None"
+                || !prefs.filter.is_empty()
+                || (regex_attribute != "None" && !prefs.regex.is_empty());
+            let numeric_attributes: Vec<String> =
+                attributes_with_numbers(&objects).into_iter().collect();
+            let mut stats_choices = vec![Choice::string("None")];
+            stats_choices.extend(Choice::strings(numeric_attributes.clone()));
+            let mut opacity_choices = vec![Choice::string("None")];
+            opacity_choices.extend(Choice::strings(numeric_attributes));
 
-            Box::new(ViewKML {
-                draw: ctx.upload(batch),
+            let mut state = ViewKML {
+                draw,
                 panel: Panel::new(Widget::col(vec![
                     Widget::row(vec![
                         Line("KML viewer").small_heading().draw(ctx),
@@ -75,60 +304,982 @@ impl ViewKML {
                     format!(
                         "{}: {} objects",
                         dataset_name,
-                        prettyprint_usize(objects.len())
+                        prettyprint_usize(total_objects)
                     )
                     .draw_text(ctx),
-                    Btn::text_fg("load KML file").build_def(ctx, lctrl(Key::L)),
+                    geometry_summary.draw_text(ctx),
+                    sample_warning,
+                    outside_summary,
+                    Widget::row(vec![
+                        Btn::text_fg("load KML file").build_def(ctx, lctrl(Key::L)),
+                        Btn::text_fg("compare with another file").build_def(ctx, None),
+                    ]),
                     Widget::row(vec![
                         "Query:".draw_text(ctx),
-                        Widget::dropdown(ctx, "query", "None".to_string(), choices),
+                        Widget::dropdown(ctx, "query", query, choices)
+                            .accessible(AccessRole::List, "Query"),
                     ]),
                     Widget::row(vec![
                         "Key=value filter:".draw_text(ctx),
-                        Widget::text_entry(ctx, String::new(), false).named("filter"),
+                        Widget::text_entry(ctx, prefs.filter.clone(), false)
+                            .named("filter")
+                            .accessible(AccessRole::TextBox, "Key=value filter"),
+                    ]),
+                    Widget::row(vec![
+                        "Regex on attribute:".draw_text(ctx),
+                        Widget::dropdown(ctx, "regex attribute", regex_attribute, regex_choices)
+                            .accessible(AccessRole::List, "Regex attribute"),
+                        Widget::text_entry(ctx, prefs.regex.clone(), false)
+                            .named("regex")
+                            .accessible(AccessRole::TextBox, "Regex"),
                     ]),
                     "Query matches 0 objects".draw_text(ctx).named("matches"),
+                    "Hold Shift and drag to select objects"
+                        .draw_text(ctx)
+                        .named("selection"),
+                    Widget::row(vec![
+                        "Time attribute:".draw_text(ctx),
+                        Widget::text_entry(ctx, prefs.time_attribute.clone(), false)
+                            .named("time attribute")
+                            .accessible(AccessRole::TextBox, "Time attribute"),
+                        Btn::text_fg("animate by time").build_def(ctx, None),
+                    ]),
+                    Text::new().draw(ctx).named("timeline"),
+                    Widget::row(vec![
+                        "Join CSV on attribute:".draw_text(ctx),
+                        Widget::text_entry(ctx, String::new(), false)
+                            .named("join key")
+                            .accessible(AccessRole::TextBox, "Join CSV on attribute"),
+                        Btn::text_fg("join CSV file").build_def(ctx, None),
+                    ]),
+                    Text::new().draw(ctx).named("join results"),
+                    Widget::row(vec![
+                        "Summarize attribute:".draw_text(ctx),
+                        Widget::dropdown(ctx, "stats attribute", "None".to_string(), stats_choices)
+                            .accessible(AccessRole::List, "Summarize attribute"),
+                    ]),
+                    Text::new().draw(ctx).named("attribute stats"),
+                    Widget::row(vec![
+                        "Opacity by attribute:".draw_text(ctx),
+                        Widget::dropdown(
+                            ctx,
+                            "opacity attribute",
+                            "None".to_string(),
+                            opacity_choices,
+                        )
+                        .accessible(AccessRole::List, "Opacity attribute"),
+                    ]),
+                    Widget::row(vec![
+                        "Opacity when missing (%):".draw_text(ctx),
+                        Spinner::new(ctx, (0, 100), DEFAULT_MISSING_OPACITY)
+                            .named("default opacity"),
+                    ]),
+                    Checkbox::switch(ctx, "Show heatmap", None, false),
+                    Text::new().draw(ctx).named("heatmap"),
+                    Btn::text_fg("count points per block").build_def(ctx, None),
+                    Widget::row(vec![
+                        "Drag objects up to this size (m):".draw_text(ctx),
+                        Spinner::new(ctx, (0, 1000), DEFAULT_MAX_DRAG_SIZE).named("max drag size"),
+                    ]),
+                    Text::new().draw(ctx).named("block counts"),
+                    Text::new().draw(ctx).named("edits"),
                 ]))
                 .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+                .keyboard_focus()
                 .build(ctx),
+                path,
+                dataset_name,
                 objects,
+                total_objects,
                 quadtree,
                 selected: None,
                 draw_query: Drawable::empty(ctx),
-            })
+                search: AttributeSearch::default(),
+                draw_outside: ctx.upload(outside_batch),
+                lasso: None,
+                selection: BTreeSet::new(),
+                draw_selection: Drawable::empty(ctx),
+                timeline: None,
+                heatmap: None,
+                block_counts: None,
+                stats_attribute: "None".to_string(),
+                opacity_attribute: "None".to_string(),
+                default_opacity: DEFAULT_MISSING_OPACITY,
+                edited: BTreeSet::new(),
+                draw_edited: Drawable::empty(ctx),
+                drag: None,
+                buffer_distance: 0,
+                saved_preferences: prefs,
+            };
+            if let Some((center, zoom)) = state.saved_preferences.camera {
+                ctx.canvas.cam_zoom = zoom;
+                ctx.canvas.center_on_map_pt(center);
+            }
+            if restore_query {
+                state.update_query(ctx, app);
+            }
+            Box::new(state)
         })
     }
+
+    fn update_query(&mut self, ctx: &mut EventCtx, app: &App) {
+        let mut query: String = self.panel.dropdown_value("query");
+        let filter = self.panel.text_box("filter");
+        self.search = AttributeSearch::default();
+        if query == "None" && !filter.is_empty() {
+            self.search.filter = Some(filter.clone());
+            query = filter;
+        }
+        let attribute: String = self.panel.dropdown_value("regex attribute");
+        let pattern = self.panel.text_box("regex");
+        let regex = if attribute == "None" || pattern.is_empty() {
+            None
+        } else {
+            match Regex::new(&pattern) {
+                Ok(re) => Some(re),
+                Err(err) => {
+                    self.draw_query = Drawable::empty(ctx);
+                    let lines = err
+                        .to_string()
+                        .lines()
+                        .map(|line| Line(line).fg(Color::RED))
+                        .collect();
+                    self.panel
+                        .replace(ctx, "matches", Text::from_multiline(lines).draw(ctx));
+                    return;
+                }
+            }
+        };
+        let (batch, cnt) = make_query(
+            app,
+            &self.objects,
+            &query,
+            regex.as_ref().map(|re| (attribute.as_str(), re)),
+        );
+        self.search.regex = regex.map(|re| (attribute, re));
+        self.draw_query = ctx.upload(batch);
+        self.panel.replace(
+            ctx,
+            "matches",
+            format!("Query matches {} objects", cnt).draw_text(ctx),
+        );
+    }
+
+    /// Merges the columns of each CSV row into the objects whose `key` attribute matches the
+    /// row's `key` column.
+    fn join_csv(&mut self, ctx: &mut EventCtx, app: &App, path: String, key: &str) {
+        let rows: Vec<BTreeMap<String, String>> = match abstutil::read_csv(path.clone()) {
+            Ok(rows) => rows,
+            Err(err) => {
+                self.panel.replace(
+                    ctx,
+                    "join results",
+                    format!("Couldn't read {}: {}", path, err).draw_text(ctx),
+                );
+                return;
+            }
+        };
+        let num_rows = rows.len();
+        let mut by_key: HashMap<String, BTreeMap<String, String>> = HashMap::new();
+        for mut row in rows {
+            if let Some(value) = row.remove(key) {
+                by_key.insert(value, row);
+            }
+        }
+
+        let mut matched_rows = HashSet::new();
+        let mut matched_objects = 0;
+        for obj in &mut self.objects {
+            if let Some(row) = obj.attribs.get(key).and_then(|value| by_key.get(value)) {
+                matched_rows.insert(obj.attribs[key].clone());
+                matched_objects += 1;
+                for (k, v) in row {
+                    obj.attribs.insert(k.clone(), v.clone());
+                }
+            }
+        }
+
+        self.panel.replace(
+            ctx,
+            "join results",
+            format!(
+                "Joined {} objects; {} of {} CSV rows didn't match",
+                prettyprint_usize(matched_objects),
+                prettyprint_usize(num_rows - matched_rows.len()),
+                prettyprint_usize(num_rows)
+            )
+            .draw_text(ctx),
+        );
+        // The filter might use the new attributes
+        self.update_query(ctx, app);
+    }
+
+    fn apply_edits(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        idx: usize,
+        changes: BTreeMap<String, String>,
+    ) {
+        if changes.is_empty() {
+            return;
+        }
+        self.objects[idx].attribs.extend(changes);
+        self.edited.insert(idx);
+        self.update_edits(ctx);
+        // The filter might match the new values
+        self.update_query(ctx, app);
+        self.update_stats(ctx);
+    }
+
+    /// Moves an object by hand. Its GPS points move too, so saving edits writes the new position.
+    fn move_object(&mut self, ctx: &mut EventCtx, app: &App, idx: usize, dx: f64, dy: f64) {
+        let gps_bounds = app.primary.map.get_gps_bounds();
+        let obj = &mut self.objects[idx];
+        obj.polygon = obj.polygon.translate(dx, dy);
+        obj.point = obj.point.map(|pt| pt.offset(dx, dy));
+        obj.gps_points = gps_bounds.convert_back(
+            &gps_bounds
+                .convert(&obj.gps_points)
+                .into_iter()
+                .map(|pt| pt.offset(dx, dy))
+                .collect(),
+        );
+        let (old_dx, old_dy) = obj.moved_by.unwrap_or((0.0, 0.0));
+        obj.moved_by = Some((old_dx + dx, old_dy + dy));
+        self.edited.insert(idx);
+
+        self.quadtree = QuadTree::default(app.primary.map.get_bounds().as_bbox());
+        for (idx, obj) in self.objects.iter().enumerate() {
+            self.quadtree
+                .insert_with_box(idx, obj.polygon.get_bounds().as_bbox());
+        }
+        self.redraw_objects(ctx);
+        self.update_edits(ctx);
+        // The query and selection highlight the old shape
+        self.update_query(ctx, app);
+        self.update_selection(ctx, app);
+    }
+
+    /// Small enough to drag, according to the panel's limit?
+    fn can_drag(&self, idx: usize) -> bool {
+        let obj = &self.objects[idx];
+        let bounds = obj.polygon.get_bounds();
+        obj.geometry == GeometryType::Point
+            || bounds.width().max(bounds.height()) <= self.panel.spinner("max drag size") as f64
+    }
+
+    fn update_edits(&mut self, ctx: &mut EventCtx) {
+        let mut batch = GeomBatch::new();
+        for idx in &self.edited {
+            if let Ok(outline) = self.objects[*idx].polygon.to_outline(THICKNESS) {
+                batch.push(Color::YELLOW, outline);
+            }
+        }
+        self.draw_edited = ctx.upload(batch);
+        self.panel.replace(
+            ctx,
+            "edits",
+            Widget::row(vec![
+                format!("{} objects edited", prettyprint_usize(self.edited.len())).draw_text(ctx),
+                // Saving would overwrite the file with just the sample
+                if self.objects.len() < self.total_objects {
+                    Btn::text_fg("save edits").inactive(ctx)
+                } else {
+                    Btn::text_fg("save edits").build_def(ctx, None)
+                },
+            ]),
+        );
+    }
+
+    /// Fades out objects with low values of the chosen attribute.
+    fn update_opacity(&mut self, ctx: &mut EventCtx) {
+        let values: Vec<Option<f64>> = self
+            .objects
+            .iter()
+            .map(|obj| abstutil::parse_loose_f64(obj.attribs.get(&self.opacity_attribute)?))
+            .collect();
+        let min = values
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f64::INFINITY, f64::min);
+        let max = values
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let (low, high) = OPACITY_RANGE;
+        for (obj, value) in self.objects.iter_mut().zip(values) {
+            let alpha = match value {
+                _ if self.opacity_attribute == "None" => high,
+                Some(x) if max > min => low + (high - low) * ((x - min) / (max - min)) as f32,
+                Some(_) => high,
+                None => self.default_opacity as f32 / 100.0,
+            };
+            obj.color = obj.color.alpha(alpha);
+        }
+        self.redraw_objects(ctx);
+    }
+
+    /// After objects change color or position
+    fn redraw_objects(&mut self, ctx: &mut EventCtx) {
+        // Objects on the timeline are drawn separately, and the one being dragged is drawn by
+        // itself
+        let dragging = self.drag.as_ref().map(|drag| drag.idx);
+        let timed: HashSet<usize> = match self.timeline {
+            Some(ref timeline) => timeline.events.iter().map(|(_, idx)| *idx).collect(),
+            None => HashSet::new(),
+        };
+        let mut batch = GeomBatch::new();
+        for (idx, obj) in self.objects.iter().enumerate() {
+            if !timed.contains(&idx) && dragging != Some(idx) {
+                batch.push(obj.color, obj.polygon.clone());
+            }
+        }
+        self.draw = ctx.upload(batch);
+        if self.timeline.is_some() {
+            self.update_timeline(ctx);
+        }
+    }
+
+    fn update_stats(&mut self, ctx: &mut EventCtx) {
+        let widget = if self.stats_attribute == "None" {
+            Text::new().draw(ctx)
+        } else if let Some(stats) = AttributeStats::new(&self.objects, &self.stats_attribute) {
+            Text::from_multiline(vec![
+                Line(format!(
+                    "{} values, {} missing or not numbers",
+                    prettyprint_usize(stats.count),
+                    prettyprint_usize(stats.missing)
+                )),
+                Line(format!(
+                    "Min {:.2}, max {:.2}, mean {:.2}, median {:.2}",
+                    stats.min, stats.max, stats.mean, stats.median
+                )),
+                Line(format!("Standard deviation {:.2}", stats.std_dev)),
+            ])
+            .draw(ctx)
+        } else {
+            format!("No numeric values for {}", self.stats_attribute).draw_text(ctx)
+        };
+        self.panel
+            .replace(ctx, "attribute stats", widget.named("attribute stats"));
+    }
+
+    /// Writes every loaded object, with its current attributes, to a new file next to the input.
+    /// Returns the path.
+    fn save_edits(&self, app: &App) -> String {
+        let name = if self.dataset_name.ends_with("_edited") {
+            self.dataset_name.clone()
+        } else {
+            format!("{}_edited", self.dataset_name)
+        };
+        let path = abstutil::path(format!(
+            "input/{}/{}.bin",
+            app.primary.map.get_city_name(),
+            name
+        ));
+        let shapes = ExtraShapes {
+            shapes: self
+                .objects
+                .iter()
+                .map(|obj| ExtraShape {
+                    points: obj.gps_points.clone(),
+                    attributes: obj.attribs.clone(),
+                })
+                .collect(),
+        };
+        abstutil::write_binary(path.clone(), &shapes);
+        path
+    }
+
+    /// Matches each selected object to a building or border, then writes the matches as trip
+    /// endpoints that simulation input can refer to. Parcels use their building; anything else
+    /// snaps to whatever's closest. Returns the path and how many objects matched.
+    fn export_trip_endpoints(&self, app: &App) -> (String, usize) {
+        let map = &app.primary.map;
+        let mut closest: FindClosest<TripEndpoint> = FindClosest::new(map.get_bounds());
+        for b in map.all_buildings() {
+            closest.add(TripEndpoint::Bldg(b.id), b.polygon.points());
+        }
+        for i in map.all_intersections() {
+            if i.is_border() {
+                closest.add(TripEndpoint::Border(i.id), i.polygon.points());
+            }
+        }
+
+        let mut endpoints = BTreeSet::new();
+        let mut matched = 0;
+        for idx in &self.selection {
+            let obj = &self.objects[*idx];
+            let endpt = if let Some(b) = obj.osm_bldg {
+                Some(TripEndpoint::Bldg(b))
+            } else {
+                let pt = obj.point.unwrap_or_else(|| obj.polygon.center());
+                closest
+                    .closest_pt(pt, MAX_SNAP_DISTANCE)
+                    .map(|(endpt, _)| endpt)
+            };
+            if let Some(endpt) = endpt {
+                endpoints.insert(endpt);
+                matched += 1;
+            }
+        }
+
+        let path = abstutil::path(format!(
+            "input/{}/{}_trip_endpoints.json",
+            map.get_city_name(),
+            self.dataset_name
+        ));
+        abstutil::write_json(path.clone(), &endpoints);
+        (path, matched)
+    }
+
+    /// The attributes whose every non-empty value in the dataset is a number.
+    fn numeric_attributes(&self) -> BTreeSet<String> {
+        let mut numeric = BTreeSet::new();
+        let mut not_numeric = BTreeSet::new();
+        for obj in &self.objects {
+            for (k, v) in &obj.attribs {
+                if v.is_empty() || not_numeric.contains(k) {
+                    continue;
+                }
+                if abstutil::parse_loose_f64(v).is_some() {
+                    numeric.insert(k.clone());
+                } else {
+                    numeric.remove(k);
+                    not_numeric.insert(k.clone());
+                }
+            }
+        }
+        numeric
+    }
+
+    /// Only called when leaving the viewer or starting an animation, so typing in the filter
+    /// doesn't write a file per keystroke. Skips the write if nothing changed.
+    fn save_preferences(&mut self, ctx: &EventCtx) {
+        let prefs = Preferences {
+            query: self.panel.dropdown_value("query"),
+            filter: self.panel.text_box("filter"),
+            regex_attribute: self.panel.dropdown_value("regex attribute"),
+            regex: self.panel.text_box("regex"),
+            time_attribute: self.panel.text_box("time attribute"),
+            camera: Some((ctx.canvas.center_to_map_pt(), ctx.canvas.cam_zoom)),
+        };
+        if prefs != self.saved_preferences {
+            abstutil::write_json(Preferences::path(&self.dataset_name), &prefs);
+            self.saved_preferences = prefs;
+        }
+    }
+
+    fn select_in_lasso(&mut self, ctx: &mut EventCtx, app: &App, lasso: Polygon) {
+        self.selection.clear();
+        for &(idx, _, _) in &self.quadtree.query(lasso.get_bounds().as_bbox()) {
+            let obj = &self.objects[*idx];
+            // Partly inside the lasso, or the lasso itself is inside the object
+            if obj.polygon.points().iter().any(|pt| lasso.contains_pt(*pt))
+                || lasso.points().iter().any(|pt| obj.polygon.contains_pt(*pt))
+            {
+                self.selection.insert(*idx);
+            }
+        }
+        self.update_selection(ctx, app);
+    }
+
+    fn selection_bounds(&self) -> Option<Bounds> {
+        if self.selection.is_empty() {
+            return None;
+        }
+        let mut bounds = Bounds::new();
+        for idx in &self.selection {
+            bounds.union(self.objects[*idx].polygon.get_bounds());
+        }
+        Some(bounds)
+    }
+
+    fn update_selection(&mut self, ctx: &mut EventCtx, app: &App) {
+        let mut batch = GeomBatch::new();
+        for idx in &self.selection {
+            batch.push(Color::CYAN.alpha(0.8), self.objects[*idx].polygon.clone());
+        }
+
+        let widget = if let Some(bounds) = self.selection_bounds() {
+            if let Ok(outline) = bounds.get_rectangle().to_outline(THICKNESS) {
+                batch.push(Color::ORANGE, outline);
+            }
+            // Hugs clustered or elongated selections much better than the bounding box
+            let hull = Polygon::convex_hull(
+                self.selection
+                    .iter()
+                    .map(|idx| self.objects[*idx].polygon.clone())
+                    .collect(),
+            );
+            if let Ok(outline) = hull.to_outline(THICKNESS) {
+                batch.push(Color::PINK, outline);
+            }
+            let bounds_area = bounds.width() * bounds.height();
+            let hull_summary = if bounds_area > 0.0 {
+                format!(
+                    "Convex hull: {} square meters, {:.0}% of the bounding box",
+                    prettyprint_usize(hull.area() as usize),
+                    100.0 * hull.area() / bounds_area
+                )
+            } else {
+                format!(
+                    "Convex hull: {} square meters",
+                    prettyprint_usize(hull.area() as usize)
+                )
+            };
+            let buffer_summary = self.buffer_selection(&mut batch);
+            let center = bounds.center().to_gps(app.primary.map.get_gps_bounds());
+            Widget::col(vec![
+                Widget::row(vec![
+                    format!(
+                        "{} objects selected",
+                        prettyprint_usize(self.selection.len())
+                    )
+                    .draw_text(ctx),
+                    Btn::text_fg("clear selection").build_def(ctx, None),
+                ]),
+                format!(
+                    "Bounding box: {:.1}m x {:.1}m, centered at ({:.5}, {:.5})",
+                    bounds.width(),
+                    bounds.height(),
+                    center.x(),
+                    center.y()
+                )
+                .draw_text(ctx),
+                hull_summary.draw_text(ctx),
+                Widget::row(vec![
+                    "Buffer by (meters):".draw_text(ctx),
+                    Spinner::new(ctx, (-100, 100), self.buffer_distance).named("buffer distance"),
+                ]),
+                buffer_summary.draw_text(ctx),
+                Widget::row(vec![
+                    Btn::text_fg("zoom to selection").build_def(ctx, None),
+                    Btn::text_fg("export as trip endpoints").build_def(ctx, None),
+                ]),
+            ])
+        } else {
+            "Hold Shift and drag to select objects".draw_text(ctx)
+        };
+        self.draw_selection = ctx.upload(batch);
+        self.panel.replace(ctx, "selection", widget);
+    }
+
+    /// Draws the selected objects grown or shrunk by `buffer_distance`, and describes the result.
+    fn buffer_selection(&self, batch: &mut GeomBatch) -> String {
+        if self.buffer_distance == 0 {
+            return "Set a distance to buffer the selection".to_string();
+        }
+        if self.selection.len() > MAX_BUFFERED_OBJECTS {
+            return format!(
+                "Select at most {} objects to buffer them",
+                prettyprint_usize(MAX_BUFFERED_OBJECTS)
+            );
+        }
+
+        let distance = Distance::meters(self.buffer_distance as f64);
+        let mut area = 0.0;
+        let mut failed = 0;
+        for idx in &self.selection {
+            let obj = &self.objects[*idx];
+            let result = match obj.point {
+                // Points are drawn as circles that don't have a ring to grow, and can't shrink
+                Some(pt) if distance > Distance::ZERO => {
+                    Ok(vec![Circle::new(pt, distance).to_polygon()])
+                }
+                Some(_) => Ok(Vec::new()),
+                None => obj.polygon.buffer(distance),
+            };
+            match result {
+                Ok(polygons) => {
+                    for p in polygons {
+                        area += p.area();
+                        if let Ok(outline) = p.to_outline(THICKNESS) {
+                            batch.push(Color::GREEN, outline);
+                        }
+                        batch.push(Color::GREEN.alpha(0.3), p);
+                    }
+                }
+                Err(_) => {
+                    failed += 1;
+                }
+            }
+        }
+
+        let mut summary = format!(
+            "Buffered by {}m: {} square meters",
+            self.buffer_distance,
+            prettyprint_usize(area as usize)
+        );
+        if self.selection.len() > 1 {
+            summary.push_str(", counting overlaps twice");
+        }
+        if failed > 0 {
+            summary.push_str(&format!(
+                " ({} objects aren't polygons and were skipped)",
+                prettyprint_usize(failed)
+            ));
+        }
+        summary
+    }
+
+    fn start_timeline(&mut self, ctx: &mut EventCtx, attribute: &str) {
+        let mut events = Vec::new();
+        for (idx, obj) in self.objects.iter().enumerate() {
+            if let Some(t) = obj.attribs.get(attribute).and_then(|x| parse_time(x)) {
+                events.push((t, idx));
+            }
+        }
+        events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        // Only draw the objects that aren't on the timeline statically
+        let timed: HashSet<usize> = events.iter().map(|(_, idx)| *idx).collect();
+        let mut batch = GeomBatch::new();
+        for (idx, obj) in self.objects.iter().enumerate() {
+            if !timed.contains(&idx) {
+                batch.push(obj.color, obj.polygon.clone());
+            }
+        }
+        self.draw = ctx.upload(batch);
+
+        let num_events = events.len();
+        self.timeline = Some(Timeline {
+            events,
+            current_pct: 0.0,
+            playing: false,
+            draw: Drawable::empty(ctx),
+        });
+        self.update_timeline(ctx);
+
+        let widget = if num_events == 0 {
+            format!("No objects have a {} that looks like a time", attribute).draw_text(ctx)
+        } else {
+            Widget::col(vec![
+                format!(
+                    "{} of {} objects have a time",
+                    prettyprint_usize(num_events),
+                    prettyprint_usize(self.objects.len())
+                )
+                .draw_text(ctx),
+                Widget::row(vec![
+                    Btn::text_fg("play / pause").build_def(ctx, Key::Space),
+                    Btn::text_fg("stop animation").build_def(ctx, None),
+                ]),
+                Slider::area(ctx, 0.15 * ctx.canvas.window_width, 0.0).named("time"),
+                "Nothing yet".draw_text(ctx).named("current time"),
+            ])
+        };
+        self.panel.replace(ctx, "timeline", widget);
+    }
+
+    fn stop_timeline(&mut self, ctx: &mut EventCtx) {
+        self.timeline = None;
+        let mut batch = GeomBatch::new();
+        for obj in &self.objects {
+            batch.push(obj.color, obj.polygon.clone());
+        }
+        self.draw = ctx.upload(batch);
+        self.panel
+            .replace(ctx, "timeline", Text::new().draw(ctx).named("timeline"));
+    }
+
+    /// Pass in None to hide the heatmap.
+    fn update_heatmap(&mut self, ctx: &mut EventCtx, app: &App, opts: Option<HeatmapOptions>) {
+        let opts = match opts {
+            Some(opts) => opts,
+            None => {
+                self.heatmap = None;
+                self.panel
+                    .replace(ctx, "heatmap", Text::new().draw(ctx).named("heatmap"));
+                return;
+            }
+        };
+        let pts = self.objects.iter().filter_map(|obj| obj.point).collect();
+        let mut batch = GeomBatch::new();
+        let legend = make_heatmap(ctx, &mut batch, app.primary.map.get_bounds(), pts, &opts);
+        self.panel
+            .replace(ctx, "heatmap", Widget::col(opts.to_controls(ctx, legend)));
+        self.heatmap = Some(Heatmap {
+            opts,
+            zoom: ctx.canvas.cam_zoom,
+            manual_resolution: false,
+            draw: ctx.upload(batch),
+        });
+    }
+
+    /// Spatially joins point objects against the blocks of buildings, coloring each block by the
+    /// number of points inside.
+    fn count_per_block(&mut self, ctx: &mut EventCtx, app: &App) {
+        let shapes = block_shapes(app);
+        let mut quadtree = QuadTree::default(app.primary.map.get_bounds().as_bbox());
+        for (idx, shape) in shapes.iter().enumerate() {
+            quadtree.insert_with_box(idx, shape.get_bounds().as_bbox());
+        }
+
+        let mut counts = vec![0; shapes.len()];
+        let mut outside = 0;
+        for pt in self.objects.iter().filter_map(|obj| obj.point) {
+            let bbox = Circle::new(pt, Distance::meters(1.0))
+                .get_bounds()
+                .as_bbox();
+            // Blocks might overlap; just count the point in the first one
+            match quadtree
+                .query(bbox)
+                .into_iter()
+                .find(|(idx, _, _)| shapes[**idx].contains_pt(pt))
+            {
+                Some((idx, _, _)) => {
+                    counts[*idx] += 1;
+                }
+                None => {
+                    outside += 1;
+                }
+            }
+        }
+        let blocks: Vec<(Polygon, usize)> = shapes.into_iter().zip(counts).collect();
+
+        let max_count = blocks.iter().map(|(_, cnt)| *cnt).max().unwrap_or(0);
+        let mut batch = GeomBatch::new();
+        for (shape, cnt) in &blocks {
+            if *cnt > 0 {
+                let pct = (*cnt as f64) / (max_count as f64);
+                batch.push(app.cs.good_to_bad_red.eval(pct).alpha(0.8), shape.clone());
+            }
+        }
+
+        let mut ranked: Vec<(usize, usize)> = blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, cnt))| *cnt > 0)
+            .map(|(idx, (_, cnt))| (idx, *cnt))
+            .collect();
+        ranked.sort_by_key(|(_, cnt)| std::cmp::Reverse(*cnt));
+        let mut table = Text::new();
+        for (idx, cnt) in ranked.iter().take(10) {
+            let center = blocks[*idx]
+                .0
+                .center()
+                .to_gps(app.primary.map.get_gps_bounds());
+            table.add(Line(format!(
+                "Block #{} at ({:.5}, {:.5}): {}",
+                idx,
+                center.x(),
+                center.y(),
+                prettyprint_usize(*cnt)
+            )));
+        }
+
+        self.panel.replace(
+            ctx,
+            "block counts",
+            Widget::col(vec![
+                ColorLegend::gradient(
+                    ctx,
+                    &app.cs.good_to_bad_red,
+                    vec!["0".to_string(), prettyprint_usize(max_count)],
+                ),
+                format!(
+                    "{} blocks contain points; {} points aren't in any block",
+                    prettyprint_usize(ranked.len()),
+                    prettyprint_usize(outside)
+                )
+                .draw_text(ctx)
+                .with_tooltip(Text::from(Line(
+                    "Blocks are the buildings sharing one loop of sidewalks. Only point objects \
+                     are counted.",
+                ))),
+                table.draw(ctx),
+                Widget::row(vec![
+                    Btn::text_fg("export block counts").build_def(ctx, None),
+                    Btn::text_fg("hide block counts").build_def(ctx, None),
+                ]),
+            ]),
+        );
+        self.block_counts = Some(BlockCounts {
+            blocks,
+            draw: ctx.upload(batch),
+        });
+    }
+
+    fn export_block_counts(&self, app: &App) -> String {
+        let path = format!(
+            "block_counts_{}_{}.csv",
+            self.dataset_name,
+            app.primary.map.get_name().as_filename()
+        );
+        let rows: Vec<BlockCountRow> = self
+            .block_counts
+            .as_ref()
+            .unwrap()
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(block, (shape, count))| {
+                let center = shape.center().to_gps(app.primary.map.get_gps_bounds());
+                BlockCountRow {
+                    block,
+                    longitude: center.x(),
+                    latitude: center.y(),
+                    count: *count,
+                }
+            })
+            .collect();
+        abstutil::write_csv(path.clone(), &rows);
+        path
+    }
+
+    fn update_timeline(&mut self, ctx: &mut EventCtx) {
+        let timeline = self.timeline.as_mut().unwrap();
+        let mut batch = GeomBatch::new();
+        let mut latest = None;
+        if let (Some(first), Some(last)) = (timeline.events.first(), timeline.events.last()) {
+            let span = last.0 - first.0;
+            let cutoff = first.0 + timeline.current_pct * span;
+            for (t, idx) in &timeline.events {
+                if *t > cutoff {
+                    break;
+                }
+                // Highlight the objects that just appeared
+                let color = if cutoff - *t <= 0.02 * span {
+                    Color::YELLOW
+                } else {
+                    self.objects[*idx].color
+                };
+                batch.push(color, self.objects[*idx].polygon.clone());
+                latest = Some(*idx);
+            }
+        }
+        timeline.draw = ctx.upload(batch);
+
+        if self.panel.has_widget("current time") {
+            let label = match latest {
+                Some(idx) => format!(
+                    "Up to {}",
+                    self.objects[idx]
+                        .attribs
+                        .get(&self.panel.text_box("time attribute"))
+                        .cloned()
+                        .unwrap_or_default()
+                ),
+                None => "Nothing yet".to_string(),
+            };
+            self.panel
+                .replace(ctx, "current time", label.draw_text(ctx));
+        }
+    }
 }
 
 impl State<App> for ViewKML {
     fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        if let Some(ref mut lasso) = self.lasso {
+            if let Some(ring) = lasso.event(ctx) {
+                self.lasso = None;
+                self.select_in_lasso(ctx, app, ring.to_polygon());
+            }
+            return Transition::Keep;
+        }
+        if ctx.is_key_down(Key::LeftShift) && ctx.input.left_mouse_button_pressed() {
+            if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
+                self.lasso = Some(Lasso::new(pt));
+                self.selected = None;
+                return Transition::Keep;
+            }
+        }
+
+        if self.drag.is_some() {
+            if ctx.input.left_mouse_button_released() {
+                let drag = self.drag.take().unwrap();
+                match drag.current {
+                    Some(pt) => {
+                        self.move_object(
+                            ctx,
+                            app,
+                            drag.idx,
+                            pt.x() - drag.start.x(),
+                            pt.y() - drag.start.y(),
+                        );
+                    }
+                    None => {
+                        self.redraw_objects(ctx);
+                        self.selected = None;
+                        return Transition::Push(EditAttributes::new(
+                            ctx,
+                            drag.idx,
+                            self.objects[drag.idx].attribs.clone(),
+                            self.numeric_attributes(),
+                        ));
+                    }
+                }
+            } else if ctx.input.get_moved_mouse().is_some() {
+                if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
+                    self.drag.as_mut().unwrap().current = Some(pt);
+                }
+            }
+            return Transition::Keep;
+        }
+        if let Some(idx) = self.selected {
+            if ctx.input.left_mouse_button_pressed() && self.can_drag(idx) {
+                if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
+                    self.drag = Some(Drag {
+                        idx,
+                        start: pt,
+                        current: None,
+                        draw_original: ctx.upload(GeomBatch::from(vec![(
+                            self.objects[idx].color.alpha(0.2),
+                            self.objects[idx].polygon.clone(),
+                        )])),
+                    });
+                    self.redraw_objects(ctx);
+                    return Transition::Keep;
+                }
+            }
+        }
+
+        if let Some(ref mut timeline) = self.timeline {
+            if timeline.playing {
+                if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+                    let pct =
+                        (timeline.current_pct + dt.inner_seconds() / PLAYBACK_SECONDS).min(1.0);
+                    if pct == 1.0 {
+                        timeline.playing = false;
+                    }
+                    self.panel.slider_mut("time").set_percent(ctx, pct);
+                }
+                if timeline.playing {
+                    ctx.request_update(UpdateType::Game);
+                }
+            }
+        }
+
         ctx.canvas_movement();
+        if let Some(ref mut heatmap) = self.heatmap {
+            if !heatmap.manual_resolution && heatmap.zoom != ctx.canvas.cam_zoom {
+                heatmap.zoom = ctx.canvas.cam_zoom;
+                let resolution = heatmap_resolution(ctx, app);
+                if resolution != heatmap.opts.resolution() {
+                    let mut opts = heatmap.opts.clone();
+                    opts.set_resolution(resolution);
+                    self.update_heatmap(ctx, app, Some(opts));
+                }
+            }
+        }
         if ctx.redo_mouseover() {
             self.selected = None;
             if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
-                for &(idx, _, _) in &self.quadtree.query(
-                    Circle::new(pt, Distance::meters(3.0))
-                        .get_bounds()
-                        .as_bbox(),
-                ) {
-                    if self.objects[*idx].polygon.contains_pt(pt) {
-                        self.selected = Some(*idx);
-                        break;
-                    }
-                }
+                self.selected = object_at(&self.objects, &self.quadtree, pt);
             }
         }
         if let Some(idx) = self.selected {
             if ctx.normal_left_click() {
                 self.selected = None;
-                return Transition::Push(PopupMsg::new(
+                return Transition::Push(EditAttributes::new(
                     ctx,
-                    "Parcel",
-                    self.objects[idx]
-                        .attribs
-                        .iter()
-                        .map(|(k, v)| format!("{} = {}", k, v))
-                        .collect(),
+                    idx,
+                    self.objects[idx].attribs.clone(),
+                    self.numeric_attributes(),
                 ));
             }
         }
@@ -136,24 +1287,145 @@ impl State<App> for ViewKML {
         match self.panel.event(ctx) {
             Outcome::Clicked(x) => match x.as_ref() {
                 "close" => {
+                    self.save_preferences(ctx);
                     return Transition::Pop;
                 }
-                "load KML file" => {
+                "clear selection" => {
+                    self.selection.clear();
+                    self.update_selection(ctx, app);
+                }
+                "zoom to selection" => {
+                    let bounds = self.selection_bounds().unwrap();
+                    // Leave a little room around the edges
+                    let zoom = (0.8 * ctx.canvas.window_width / bounds.width())
+                        .min(0.8 * ctx.canvas.window_height / bounds.height())
+                        .max(ctx.canvas.min_zoom())
+                        .min(150.0);
+                    return Transition::Push(Warping::new(
+                        ctx,
+                        bounds.center(),
+                        Some(zoom),
+                        None,
+                        &mut app.primary,
+                    ));
+                }
+                "animate by time" => {
+                    let attribute = self.panel.text_box("time attribute");
+                    self.start_timeline(ctx, &attribute);
+                    self.save_preferences(ctx);
+                }
+                "play / pause" => {
+                    let timeline = self.timeline.as_mut().unwrap();
+                    timeline.playing = !timeline.playing;
+                    if timeline.playing && timeline.current_pct == 1.0 {
+                        self.panel.slider_mut("time").set_percent(ctx, 0.0);
+                    }
+                    ctx.request_update(UpdateType::Game);
+                }
+                "export as trip endpoints" => {
+                    let (path, matched) = self.export_trip_endpoints(app);
+                    let mut lines = vec![format!(
+                        "Matched {} of {} selected objects to a building or border",
+                        prettyprint_usize(matched),
+                        prettyprint_usize(self.selection.len())
+                    )];
+                    if matched < self.selection.len() {
+                        lines.push(format!(
+                            "The other {} had nothing within {}",
+                            prettyprint_usize(self.selection.len() - matched),
+                            MAX_SNAP_DISTANCE
+                        ));
+                    }
+                    lines.push(format!("Wrote the distinct endpoints to {}", path));
+                    return Transition::Push(PopupMsg::new(ctx, "Trip endpoints exported", lines));
+                }
+                "save edits" => {
+                    let path = self.save_edits(app);
+                    let lines = vec![
+                        format!(
+                            "Saved {} objects, {} of them edited, to {}",
+                            prettyprint_usize(self.objects.len()),
+                            prettyprint_usize(self.edited.len()),
+                            path
+                        ),
+                        "Objects outside the map weren't loaded, so they aren't included."
+                            .to_string(),
+                    ];
+                    return Transition::Push(PopupMsg::new(ctx, "Edits saved", lines));
+                }
+                "count points per block" => {
+                    self.count_per_block(ctx, app);
+                }
+                "hide block counts" => {
+                    self.block_counts = None;
+                    self.panel.replace(
+                        ctx,
+                        "block counts",
+                        Text::new().draw(ctx).named("block counts"),
+                    );
+                }
+                "export block counts" => {
+                    let path = self.export_block_counts(app);
+                    return Transition::Push(PopupMsg::new(
+                        ctx,
+                        "Data exported",
+                        vec![format!("Data exported to {}", path)],
+                    ));
+                }
+                "stop animation" => {
+                    self.stop_timeline(ctx);
+                }
+                "join CSV file" => {
+                    let key = self.panel.text_box("join key");
+                    if key.is_empty() {
+                        return Transition::Push(PopupMsg::new(
+                            ctx,
+                            "Join CSV",
+                            vec!["First type the attribute to join on"],
+                        ));
+                    }
                     return Transition::Push(ChooseSomething::new(
                         ctx,
-                        "Load file",
+                        "Join CSV file",
                         Choice::strings(
                             abstutil::list_dir(abstutil::path(format!(
                                 "input/{}/",
                                 app.primary.map.get_city_name()
                             )))
                             .into_iter()
-                            .filter(|x| {
-                                (x.ends_with(".bin") || x.ends_with(".kml") || x.ends_with(".csv"))
-                                    && !x.ends_with("popdat.bin")
-                            })
+                            .filter(|x| x.ends_with(".csv"))
                             .collect(),
                         ),
+                        Box::new(move |path, _, _| {
+                            let key = key.clone();
+                            Transition::Multi(vec![
+                                Transition::Pop,
+                                Transition::ModifyState(Box::new(move |state, ctx, app| {
+                                    let state = state.downcast_mut::<ViewKML>().unwrap();
+                                    state.join_csv(ctx, app, path, &key);
+                                })),
+                            ])
+                        }),
+                    ));
+                }
+                "compare with another file" => {
+                    self.save_preferences(ctx);
+                    let path = self.path.clone();
+                    return Transition::Push(ChooseSomething::new(
+                        ctx,
+                        "Compare with",
+                        Choice::strings(list_datasets(app)),
+                        Box::new(move |other, ctx, app| {
+                            Transition::Replace(CompareKML::new(ctx, app, path, Some(other)))
+                        }),
+                    ));
+                }
+                "load KML file" => {
+                    self.save_preferences(ctx);
+                    return Transition::Push(ChooseSomething::new(
+                        ctx,
+                        "Load file",
+                        Choice::strings(list_datasets(app)),
                         Box::new(|path, ctx, app| {
                             Transition::Multi(vec![
                                 Transition::Pop,
@@ -165,38 +1437,134 @@ impl State<App> for ViewKML {
                 _ => unreachable!(),
             },
             Outcome::Changed => {
-                let mut query: String = self.panel.dropdown_value("query");
-                let filter = self.panel.text_box("filter");
-                if query == "None" && !filter.is_empty() {
-                    query = filter;
+                self.update_query(ctx, app);
+
+                let attribute: String = self.panel.dropdown_value("stats attribute");
+                if attribute != self.stats_attribute {
+                    self.stats_attribute = attribute;
+                    self.update_stats(ctx);
+                }
+
+                if self.panel.has_widget("buffer distance") {
+                    let distance = self.panel.spinner("buffer distance");
+                    if distance != self.buffer_distance {
+                        self.buffer_distance = distance;
+                        self.update_selection(ctx, app);
+                    }
+                }
+
+                let attribute: String = self.panel.dropdown_value("opacity attribute");
+                let default_opacity = self.panel.spinner("default opacity");
+                if attribute != self.opacity_attribute || default_opacity != self.default_opacity {
+                    self.opacity_attribute = attribute;
+                    self.default_opacity = default_opacity;
+                    self.update_opacity(ctx);
+                }
+
+                let opts = if !self.panel.is_checked("Show heatmap") {
+                    None
+                } else if self.heatmap.is_some() {
+                    Some(HeatmapOptions::from_controls(&self.panel))
+                } else {
+                    let mut opts = HeatmapOptions::new();
+                    opts.set_resolution(heatmap_resolution(ctx, app));
+                    Some(opts)
+                };
+                if opts.as_ref() != self.heatmap.as_ref().map(|h| &h.opts) {
+                    let manual_resolution = match (&opts, &self.heatmap) {
+                        (Some(new), Some(old)) => {
+                            old.manual_resolution || new.resolution() != old.opts.resolution()
+                        }
+                        _ => false,
+                    };
+                    self.update_heatmap(ctx, app, opts);
+                    if let Some(ref mut heatmap) = self.heatmap {
+                        heatmap.manual_resolution = manual_resolution;
+                    }
                 }
-                let (batch, cnt) = make_query(app, &self.objects, &query);
-                self.draw_query = ctx.upload(batch);
-                self.panel.replace(
-                    ctx,
-                    "matches",
-                    format!("Query matches {} objects", cnt).draw_text(ctx),
-                );
             }
             _ => {}
         }
 
+        if self.timeline.is_some() && self.panel.has_widget("time") {
+            let pct = self.panel.slider("time").get_percent();
+            let timeline = self.timeline.as_mut().unwrap();
+            if pct != timeline.current_pct {
+                timeline.current_pct = pct;
+                self.update_timeline(ctx);
+            }
+        }
+
         Transition::Keep
     }
 
     fn draw(&self, g: &mut GfxCtx, app: &App) {
         g.redraw(&self.draw);
         g.redraw(&self.draw_query);
+        g.redraw(&self.draw_selection);
+        if let Some(ref timeline) = self.timeline {
+            g.redraw(&timeline.draw);
+        }
+        if let Some(ref heatmap) = self.heatmap {
+            g.redraw(&heatmap.draw);
+        }
+        if let Some(ref block_counts) = self.block_counts {
+            g.redraw(&block_counts.draw);
+        }
+        g.redraw(&self.draw_edited);
+        if self
+            .panel
+            .maybe_is_checked("show outside objects at the map edge")
+            .unwrap_or(false)
+        {
+            g.redraw(&self.draw_outside);
+        }
         self.panel.draw(g);
+        if let Some(ref lasso) = self.lasso {
+            lasso.draw(g);
+        }
 
-        if let Some(idx) = self.selected {
+        if let Some(ref drag) = self.drag {
+            g.redraw(&drag.draw_original);
+            let obj = &self.objects[drag.idx];
+            let polygon = match drag.current {
+                Some(pt) => obj
+                    .polygon
+                    .translate(pt.x() - drag.start.x(), pt.y() - drag.start.y()),
+                None => obj.polygon.clone(),
+            };
+            g.draw_polygon(Color::BLUE, polygon);
+        } else if let Some(idx) = self.selected {
             let obj = &self.objects[idx];
 
             g.draw_polygon(Color::BLUE, obj.polygon.clone());
             let mut txt = Text::new();
-            for (k, v) in &obj.attribs {
+            // Whatever made the object match a search goes first
+            let (matching, rest): (Vec<_>, Vec<_>) = obj
+                .attribs
+                .iter()
+                .partition(|(k, v)| self.search.matches(k, v));
+            for (k, v) in &matching {
+                txt.add_highlighted(
+                    Line(format!("{} = {}", k, v)).fg(Color::CYAN),
+                    Color::BLACK.alpha(0.5),
+                );
+            }
+            let shown = if self.search.is_active() {
+                rest.len()
+                    .min(MAX_TOOLTIP_ATTRIBUTES.saturating_sub(matching.len()))
+            } else {
+                rest.len()
+            };
+            for (k, v) in &rest[..shown] {
                 txt.add(Line(format!("{} = {}", k, v)));
             }
+            if shown < rest.len() {
+                txt.add(Line(format!("+{} more", rest.len() - shown)).secondary());
+            }
+            if let Some((dx, dy)) = obj.moved_by {
+                txt.add(Line(format!("Moved by ({:.1}, {:.1}) meters", dx, dy)).fg(Color::YELLOW));
+            }
             g.draw_mouse_tooltip(txt);
 
             if let Some(b) = obj.osm_bldg {
@@ -206,13 +1574,334 @@ impl State<App> for ViewKML {
     }
 }
 
-/// Loads and clips objects to the current map. Also returns the dataset name.
+/// Shows two datasets on either side of a divider that can be dragged. Both sides share one camera,
+/// so the same place lines up across the divider, and each side has its own selection.
+pub struct CompareKML {
+    panel: Panel,
+    sides: Vec<CompareSide>,
+    // As a fraction of the window width, so resizing keeps the split
+    divider_pct: f64,
+    dragging_divider: bool,
+}
+
+struct CompareSide {
+    path: Option<String>,
+    dataset_name: String,
+    objects: Vec<Object>,
+    quadtree: QuadTree<usize>,
+    draw: Drawable,
+    hovering: Option<usize>,
+    selected: Option<usize>,
+    attributes: Panel,
+}
+
+const DIVIDER_THICKNESS: f64 = 4.0;
+// How close to the divider the cursor has to be to grab it, in pixels
+const DIVIDER_GRAB_DISTANCE: f64 = 10.0;
+
+impl CompareKML {
+    pub fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        left: Option<String>,
+        right: Option<String>,
+    ) -> Box<dyn State<App>> {
+        ctx.loading_screen("load KML files to compare", |ctx, mut timer| {
+            let sides = vec![
+                CompareSide::new(ctx, app, left, HorizontalAlignment::LeftInset, &mut timer),
+                CompareSide::new(ctx, app, right, HorizontalAlignment::RightInset, &mut timer),
+            ];
+            Box::new(CompareKML {
+                panel: Panel::new(Widget::col(vec![
+                    Widget::row(vec![
+                        Line("Compare KML files").small_heading().draw(ctx),
+                        Btn::close(ctx),
+                    ]),
+                    format!(
+                        "{} on the left, {} on the right",
+                        sides[0].dataset_name, sides[1].dataset_name
+                    )
+                    .draw_text(ctx),
+                    "Drag the divider to move the split".draw_text(ctx),
+                    Btn::text_fg("swap sides").build_def(ctx, None),
+                ]))
+                .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+                .build(ctx),
+                sides,
+                divider_pct: 0.5,
+                dragging_divider: false,
+            })
+        })
+    }
+
+    fn divider_x(&self, canvas: &Canvas) -> f64 {
+        self.divider_pct * canvas.window_width
+    }
+
+    fn side_at(&self, canvas: &Canvas, pt: ScreenPt) -> usize {
+        if pt.x < self.divider_x(canvas) {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+impl CompareSide {
+    fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        path: Option<String>,
+        align: HorizontalAlignment,
+        timer: &mut Timer,
+    ) -> CompareSide {
+        let (dataset_name, mut objects, _) = load_objects(app, path.clone(), false, timer);
+        if objects.len() > MAX_OBJECTS {
+            objects = sample_evenly(objects, MAX_OBJECTS);
+        }
+        let (draw, quadtree) = render_objects(ctx, app, &objects, timer);
+        let mut side = CompareSide {
+            path,
+            dataset_name,
+            objects,
+            quadtree,
+            draw,
+            hovering: None,
+            selected: None,
+            attributes: Panel::empty(ctx),
+        };
+        side.update_attributes(ctx, align);
+        side
+    }
+
+    fn update_attributes(&mut self, ctx: &mut EventCtx, align: HorizontalAlignment) {
+        let mut col = vec![Line(&self.dataset_name).small_heading().draw(ctx)];
+        if let Some(idx) = self.selected {
+            let mut txt = Text::new();
+            for (k, v) in &self.objects[idx].attribs {
+                txt.add(Line(format!("{} = {}", k, v)));
+            }
+            col.push(txt.draw(ctx));
+        } else {
+            col.push("Click an object to see its attributes".draw_text(ctx));
+        }
+        self.attributes = Panel::new(Widget::col(col))
+            .aligned(align, VerticalAlignment::BottomInset)
+            .build(ctx);
+    }
+}
+
+impl State<App> for CompareKML {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        if self.dragging_divider {
+            if ctx.input.left_mouse_button_released() {
+                self.dragging_divider = false;
+            } else if let Some(pt) = ctx.input.get_moved_mouse() {
+                self.divider_pct = (pt.x / ctx.canvas.window_width).max(0.0).min(1.0);
+            }
+            return Transition::Keep;
+        }
+        if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
+            let cursor = ctx.canvas.get_cursor();
+            if (cursor.x - self.divider_x(&ctx.canvas)).abs() <= DIVIDER_GRAB_DISTANCE
+                && ctx.input.left_mouse_button_pressed()
+            {
+                self.dragging_divider = true;
+                return Transition::Keep;
+            }
+
+            if ctx.redo_mouseover() {
+                for side in &mut self.sides {
+                    side.hovering = None;
+                }
+                let idx = self.side_at(&ctx.canvas, cursor);
+                let side = &mut self.sides[idx];
+                side.hovering = object_at(&side.objects, &side.quadtree, pt);
+            }
+        }
+
+        ctx.canvas_movement();
+        for (idx, side) in self.sides.iter_mut().enumerate() {
+            if side.hovering.is_some() && ctx.normal_left_click() {
+                side.selected = side.hovering;
+                let align = if idx == 0 {
+                    HorizontalAlignment::LeftInset
+                } else {
+                    HorizontalAlignment::RightInset
+                };
+                side.update_attributes(ctx, align);
+            }
+            side.attributes.event(ctx);
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Transition::Pop;
+                }
+                "swap sides" => {
+                    let left = self.sides[0].path.clone();
+                    let right = self.sides[1].path.clone();
+                    return Transition::Replace(CompareKML::new(ctx, app, right, left));
+                }
+                _ => unreachable!(),
+            },
+            _ => {}
+        }
+
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _: &App) {
+        let divider_x = self.divider_x(g.canvas);
+        let height = g.canvas.window_height;
+        for (idx, side) in self.sides.iter().enumerate() {
+            let (x1, x2) = if idx == 0 {
+                (0.0, divider_x)
+            } else {
+                (divider_x, g.canvas.window_width)
+            };
+            g.enable_clipping(ScreenRectangle {
+                x1,
+                y1: 0.0,
+                x2,
+                y2: height,
+            });
+            g.redraw(&side.draw);
+            if let Some(idx) = side.selected {
+                g.draw_polygon(Color::YELLOW, side.objects[idx].polygon.clone());
+            }
+            if let Some(idx) = side.hovering {
+                g.draw_polygon(Color::BLUE, side.objects[idx].polygon.clone());
+            }
+            g.disable_clipping();
+        }
+
+        let divider = g.upload(GeomBatch::from(vec![(
+            Color::WHITE,
+            Polygon::rectangle(DIVIDER_THICKNESS, height),
+        )]));
+        g.redraw_at(
+            ScreenPt::new(divider_x - DIVIDER_THICKNESS / 2.0, 0.0),
+            &divider,
+        );
+
+        self.panel.draw(g);
+        for side in &self.sides {
+            side.attributes.draw(g);
+        }
+    }
+}
+
+/// Edits the attributes of one object. Nothing changes in the viewer until the edits are applied.
+struct EditAttributes {
+    panel: Panel,
+    idx: usize,
+    attribs: BTreeMap<String, String>,
+    // Only accept numbers for these
+    numeric: BTreeSet<String>,
+}
+
+impl EditAttributes {
+    fn new(
+        ctx: &mut EventCtx,
+        idx: usize,
+        attribs: BTreeMap<String, String>,
+        numeric: BTreeSet<String>,
+    ) -> Box<dyn State<App>> {
+        let mut col = vec![Widget::row(vec![
+            Line("Edit attributes").small_heading().draw(ctx),
+            Btn::close(ctx),
+        ])];
+        for (k, v) in &attribs {
+            col.push(Widget::row(vec![
+                format!("{}:", k).draw_text(ctx).centered_vert(),
+                Widget::text_entry(ctx, v.clone(), false)
+                    .named(format!("value of {}", k))
+                    .accessible(AccessRole::TextBox, k.clone()),
+            ]));
+        }
+        col.push(Text::new().draw(ctx).named("error"));
+        col.push(Btn::text_bg2("Apply").build_def(ctx, None));
+        Box::new(EditAttributes {
+            panel: Panel::new(Widget::col(col))
+                .max_size(Percent::int(60), Percent::int(80))
+                .build(ctx),
+            idx,
+            attribs,
+            numeric,
+        })
+    }
+
+    /// Only the attributes that changed, or an error if a number doesn't parse.
+    fn changes(&self) -> Result<BTreeMap<String, String>, String> {
+        let mut changes = BTreeMap::new();
+        for (k, old) in &self.attribs {
+            let value = self.panel.text_box(&format!("value of {}", k));
+            if value == *old {
+                continue;
+            }
+            if self.numeric.contains(k)
+                && !value.is_empty()
+                && abstutil::parse_loose_f64(&value).is_none()
+            {
+                return Err(format!("{} should be a number, not {:?}", k, value));
+            }
+            changes.insert(k.clone(), value);
+        }
+        Ok(changes)
+    }
+}
+
+impl State<App> for EditAttributes {
+    fn event(&mut self, ctx: &mut EventCtx, _: &mut App) -> Transition {
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => Transition::Pop,
+                "Apply" => match self.changes() {
+                    Ok(changes) => {
+                        let idx = self.idx;
+                        Transition::Multi(vec![
+                            Transition::Pop,
+                            Transition::ModifyState(Box::new(move |state, ctx, app| {
+                                let state = state.downcast_mut::<ViewKML>().unwrap();
+                                state.apply_edits(ctx, app, idx, changes);
+                            })),
+                        ])
+                    }
+                    Err(err) => {
+                        self.panel.replace(
+                            ctx,
+                            "error",
+                            Line(err).fg(Color::RED).draw(ctx).named("error"),
+                        );
+                        Transition::Keep
+                    }
+                },
+                _ => unreachable!(),
+            },
+            _ => Transition::Keep,
+        }
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        grey_out_map(g, app);
+        self.panel.draw(g);
+    }
+}
+
+/// Loads and clips objects to the current map. Also returns the dataset name and, for every
+/// object completely outside the map, its first point clamped to the nearest edge of the map.
 fn load_objects(
     app: &App,
     path: Option<String>,
     dump_clipped_shapes: bool,
     timer: &mut Timer,
-) -> (String, Vec<Object>) {
+) -> (String, Vec<Object>, Vec<Pt2D>) {
     let map = &app.primary.map;
     let bounds = map.get_gps_bounds();
 
@@ -248,48 +1937,64 @@ fn load_objects(
         .map(|b| (b.orig_id.inner().to_string(), b.id))
         .collect();
     let cs = &app.cs;
+    let map_bounds = map.get_bounds();
 
-    let pairs: Vec<(Object, ExtraShape)> = timer
-        .parallelize(
-            "convert shapes",
-            Parallelism::Fastest,
-            raw_shapes.shapes.into_iter().enumerate().collect(),
-            |(idx, shape)| {
-                let pts = bounds.convert(&shape.points);
-                if pts.iter().any(|pt| boundary.contains_pt(*pt)) {
-                    Some((
-                        make_object(
-                            cs,
-                            &bldg_lookup,
-                            shape.attributes.clone(),
-                            pts,
-                            &dataset_name,
-                            idx,
-                        ),
-                        shape,
-                    ))
-                } else {
-                    None
-                }
-            },
-        )
-        .into_iter()
-        .flatten()
-        .collect();
+    // Err means the shape is out-of-bounds, and holds its clamped first point. Shapes without any
+    // points are dropped.
+    let results: Vec<Result<(Object, ExtraShape), Option<Pt2D>>> = timer.parallelize(
+        "convert shapes",
+        Parallelism::Fastest,
+        raw_shapes.shapes.into_iter().enumerate().collect(),
+        |(idx, shape)| {
+            let pts = bounds.convert(&shape.points);
+            if pts.iter().any(|pt| boundary.contains_pt(*pt)) {
+                Ok((
+                    make_object(
+                        cs,
+                        &bldg_lookup,
+                        shape.attributes.clone(),
+                        pts,
+                        shape.points.clone(),
+                        &dataset_name,
+                        idx,
+                    ),
+                    shape,
+                ))
+            } else {
+                Err(pts.first().map(|pt| {
+                    Pt2D::new(
+                        abstutil::clamp(pt.x(), map_bounds.min_x, map_bounds.max_x),
+                        abstutil::clamp(pt.y(), map_bounds.min_y, map_bounds.max_y),
+                    )
+                }))
+            }
+        },
+    );
     let mut objects = Vec::new();
     let mut clipped_shapes = Vec::new();
-    for (obj, shape) in pairs {
-        objects.push(obj);
-        clipped_shapes.push(shape);
+    let mut outside = Vec::new();
+    for result in results {
+        match result {
+            Ok((obj, shape)) => {
+                objects.push(obj);
+                clipped_shapes.push(shape);
+            }
+            Err(Some(pt)) => {
+                outside.push(pt);
+            }
+            Err(None) => {}
+        }
     }
     if path.is_some() && dump_clipped_shapes {
         abstutil::write_binary(
             format!("{}_clipped_for_{}.bin", dataset_name, map.get_name().map),
-            &clipped_shapes,
+            &ExtraShapes {
+                shapes: clipped_shapes,
+            },
         );
     }
 
-    (dataset_name, objects)
+    (dataset_name, objects, outside)
 }
 
 fn make_object(
@@ -297,13 +2002,18 @@ fn make_object(
     bldg_lookup: &HashMap<String, BuildingID>,
     attribs: BTreeMap<String, String>,
     pts: Vec<Pt2D>,
+    gps_points: Vec<LonLat>,
     dataset_name: &str,
     obj_idx: usize,
 ) -> Object {
     let mut color = Color::RED.alpha(0.8);
+    let point = if pts.len() == 1 { Some(pts[0]) } else { None };
+    let mut geometry = GeometryType::LineString;
     let polygon = if pts.len() == 1 {
+        geometry = GeometryType::Point;
         Circle::new(pts[0], RADIUS).to_polygon()
     } else if let Ok(ring) = Ring::new(pts.clone()) {
+        geometry = GeometryType::Polygon;
         if attribs.get("spatial_type") == Some(&"Polygon".to_string()) {
             color = cs.rotating_color_plot(obj_idx).alpha(0.8);
             ring.to_polygon()
@@ -337,16 +2047,215 @@ fn make_object(
         polygon,
         color,
         attribs,
+        point,
+        geometry,
+        gps_points,
+        moved_by: None,
         osm_bldg,
     }
 }
 
-fn make_query(app: &App, objects: &Vec<Object>, query: &str) -> (GeomBatch, usize) {
+/// Like "1,204 points, 58 lines, 30 polygons"
+fn describe_geometry_types(objects: &Vec<Object>) -> String {
+    let count = |geometry| {
+        prettyprint_usize(
+            objects
+                .iter()
+                .filter(|obj| obj.geometry == geometry)
+                .count(),
+        )
+    };
+    format!(
+        "{} points, {} lines, {} polygons",
+        count(GeometryType::Point),
+        count(GeometryType::LineString),
+        count(GeometryType::Polygon)
+    )
+}
+
+/// Draws all of the objects and indexes them for mouseover.
+fn render_objects(
+    ctx: &mut EventCtx,
+    app: &App,
+    objects: &Vec<Object>,
+    timer: &mut Timer,
+) -> (Drawable, QuadTree<usize>) {
+    let mut batch = GeomBatch::new();
+    let mut quadtree = QuadTree::default(app.primary.map.get_bounds().as_bbox());
+    timer.start_iter("render shapes", objects.len());
+    for (idx, obj) in objects.iter().enumerate() {
+        timer.next();
+        quadtree.insert_with_box(idx, obj.polygon.get_bounds().as_bbox());
+        batch.push(obj.color, obj.polygon.clone());
+    }
+    timer.start("upload shapes");
+    let draw = ctx.upload(batch);
+    timer.stop("upload shapes");
+    (draw, quadtree)
+}
+
+fn object_at(objects: &Vec<Object>, quadtree: &QuadTree<usize>, pt: Pt2D) -> Option<usize> {
+    for &(idx, _, _) in &quadtree.query(
+        Circle::new(pt, Distance::meters(3.0))
+            .get_bounds()
+            .as_bbox(),
+    ) {
+        if objects[*idx].polygon.contains_pt(pt) {
+            return Some(*idx);
+        }
+    }
+    None
+}
+
+/// Files in the current city's input directory that the viewer can load.
+fn list_datasets(app: &App) -> Vec<String> {
+    abstutil::list_dir(abstutil::path(format!(
+        "input/{}/",
+        app.primary.map.get_city_name()
+    )))
+    .into_iter()
+    .filter(|x| {
+        (x.ends_with(".bin") || x.ends_with(".kml") || x.ends_with(".csv"))
+            && !x.ends_with("popdat.bin")
+    })
+    .collect()
+}
+
+/// Summary statistics for one numeric attribute. Values that don't parse count as missing.
+struct AttributeStats {
+    count: usize,
+    missing: usize,
+    min: f64,
+    max: f64,
+    mean: f64,
+    median: f64,
+    std_dev: f64,
+}
+
+impl AttributeStats {
+    /// None if no object has a numeric value for the attribute
+    fn new(objects: &Vec<Object>, attribute: &str) -> Option<AttributeStats> {
+        let mut values: Vec<f64> = objects
+            .iter()
+            .filter_map(|obj| abstutil::parse_loose_f64(obj.attribs.get(attribute)?))
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / (count as f64);
+        let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count as f64);
+        let median = if count % 2 == 0 {
+            (values[count / 2 - 1] + values[count / 2]) / 2.0
+        } else {
+            values[count / 2]
+        };
+        Some(AttributeStats {
+            count,
+            missing: objects.len() - count,
+            min: values[0],
+            max: values[count - 1],
+            mean,
+            median,
+            std_dev: variance.sqrt(),
+        })
+    }
+}
+
+/// Every attribute that appears on at least one object
+fn all_attributes(objects: &Vec<Object>) -> BTreeSet<String> {
+    let mut attributes = BTreeSet::new();
+    for obj in objects {
+        attributes.extend(obj.attribs.keys().cloned());
+    }
+    attributes
+}
+
+/// Attributes with at least one numeric value somewhere in the dataset
+fn attributes_with_numbers(objects: &Vec<Object>) -> BTreeSet<String> {
+    let mut attributes = BTreeSet::new();
+    for obj in objects {
+        for (k, v) in &obj.attribs {
+            if !attributes.contains(k) && abstutil::parse_loose_f64(v).is_some() {
+                attributes.insert(k.clone());
+            }
+        }
+    }
+    attributes
+}
+
+/// Keeps about `n` items, spread evenly through the input.
+fn sample_evenly<T>(items: Vec<T>, n: usize) -> Vec<T> {
+    let step = (items.len() as f64) / (n as f64);
+    let mut next = 0.0;
+    items
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, x)| {
+            if (idx as f64) >= next {
+                next += step;
+                Some(x)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Heatmap cells should be a few pixels across at the current zoom, without the grid getting too
+/// huge to compute when zoomed in on a big map.
+fn heatmap_resolution(ctx: &EventCtx, app: &App) -> usize {
+    let bounds = app.primary.map.get_bounds();
+    let finest = (bounds.width().max(bounds.height()) / 1000.0).ceil();
+    (8.0 / ctx.canvas.cam_zoom).max(finest).round() as usize
+}
+
+/// Understands plain numbers, RFC 3339 timestamps, "YYYY-MM-DD HH:MM:SS", and times of day like
+/// "13:00:00". All of them are turned into seconds, but the values only need to be comparable.
+fn parse_time(value: &str) -> Option<f64> {
+    if let Some(x) = abstutil::parse_loose_f64(value) {
+        return Some(x);
+    }
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(t.timestamp() as f64);
+    }
+    if let Ok(t) = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Some(t.timestamp() as f64);
+    }
+    Time::parse(value).ok().map(|t| t.inner_seconds())
+}
+
+/// Highlights objects matching a built-in query or a key=value filter. If a regex is given, only
+/// objects whose value for that attribute matches are considered; with no other query, all of them
+/// are highlighted.
+fn make_query(
+    app: &App,
+    objects: &Vec<Object>,
+    query: &str,
+    regex: Option<(&str, &Regex)>,
+) -> (GeomBatch, usize) {
     let mut batch = GeomBatch::new();
     let mut cnt = 0;
     let color = Color::BLUE.alpha(0.8);
+    let objects = objects.iter().filter(|obj| match regex {
+        Some((attribute, re)) => obj
+            .attribs
+            .get(attribute)
+            .map(|value| re.is_match(value))
+            .unwrap_or(false),
+        None => true,
+    });
     match query {
-        "None" => {}
+        "None" => {
+            if regex.is_some() {
+                for obj in objects {
+                    cnt += 1;
+                    batch.push(color, obj.polygon.clone());
+                }
+            }
+        }
         "parcels without buildings" => {
             for obj in objects {
                 if obj.osm_bldg.is_none() {
@@ -401,6 +2310,7 @@ fn make_query(app: &App, objects: &Vec<Object>, query: &str) -> (GeomBatch, usiz
             for obj in objects {
                 for (k, v) in &obj.attribs {
                     if format!("{}={}", k, v).contains(x) {
+                        cnt += 1;
                         batch.push(color, obj.polygon.clone());
                         break;
                     }
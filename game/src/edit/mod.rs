@@ -261,7 +261,7 @@ impl State<App> for EditMode {
                                 Box::new(|_, _| {}),
                             )),
                             "delete this proposal and remove all edits" => {
-                                abstutil::delete_file(abstutil::path_edits(
+                                abstutil::trash_file(abstutil::path_edits(
                                     app.primary.map.get_name(),
                                     &app.primary.map.get_edits().edits_name,
                                 ));
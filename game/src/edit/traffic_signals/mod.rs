@@ -331,9 +331,10 @@ impl State<App> for TrafficSignalEditor {
                 "Export" => {
                     for signal in BundleEdits::get_current(app, &self.members).signals {
                         let ts = signal.export(&app.primary.map);
-                        abstutil::write_json(
+                        abstutil::write_json_with(
                             format!("traffic_signal_data/{}.json", ts.intersection_osm_node_id),
                             &ts,
+                            abstutil::JsonFormat::Pretty,
                         );
                     }
                 }
@@ -450,6 +450,16 @@ impl State<App> for CommuterPatterns {
 // region bounded by 4 roads. But there are plenty of places with stranger shapes, or buildings
 // near the border of the map. The fallback is currently to just group those buildings that share
 // the same sidewalk.
+/// The shape of every block of buildings, without the pseudo-blocks for borders.
+pub fn block_shapes(app: &App) -> Vec<Polygon> {
+    let (_, _, blocks) = group_bldgs(app);
+    blocks
+        .into_iter()
+        .filter(|block| !block.bldgs.is_empty())
+        .map(|block| block.shape)
+        .collect()
+}
+
 fn group_bldgs(
     app: &App,
 ) -> (
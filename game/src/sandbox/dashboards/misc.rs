@@ -213,6 +213,9 @@ impl State<App> for TransitRoutes {
                     return Transition::Keep;
                 }
             }
+            Outcome::Reordered(_, _) => {
+                return Transition::Keep;
+            }
             Outcome::Nothing => {
                 if let Some(routes) = self.panel.autocomplete_done("search") {
                     if !routes.is_empty() {
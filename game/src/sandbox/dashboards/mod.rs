@@ -1,4 +1,4 @@
-pub use commuter::CommuterPatterns;
+pub use commuter::{block_shapes, CommuterPatterns};
 pub use traffic_signals::TrafficSignalDemand;
 pub use trip_table::FinishedTripTable;
 
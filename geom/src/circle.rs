@@ -66,7 +66,7 @@ impl Circle {
     }
 
     /// Returns the ring around the circle.
-    fn to_ring(&self) -> Ring {
+    pub(crate) fn to_ring(&self) -> Ring {
         let mut pts: Vec<Pt2D> = (0..=TRIANGLES_PER_CIRCLE)
             .map(|i| {
                 self.center.project_away(
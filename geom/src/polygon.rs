@@ -6,7 +6,7 @@ use geo::algorithm::convex_hull::ConvexHull;
 use geo_booleanop::boolean::BooleanOp;
 use serde::{Deserialize, Serialize};
 
-use crate::{Angle, Bounds, Distance, HashablePt2D, PolyLine, Pt2D, Ring};
+use crate::{Angle, Bounds, Circle, Distance, HashablePt2D, Line, PolyLine, Pt2D, Ring};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Polygon {
@@ -307,6 +307,50 @@ impl Polygon {
         mp.convex_hull().into()
     }
 
+    /// Grows the polygon outwards by `distance`, rounding the corners, or shrinks it inwards if
+    /// the distance is negative. Shrinking can split the polygon into pieces or make it vanish
+    /// entirely. Only works for polygons formed from rings, like `to_outline`. A band is built up
+    /// from one piece per edge and corner, so this gets slow for polygons with many points.
+    pub fn buffer(&self, distance: Distance) -> Result<Vec<Polygon>, String> {
+        if distance == Distance::ZERO {
+            return Ok(vec![self.clone()]);
+        }
+        let rings = match self.rings {
+            Some(ref rings) => rings.clone(),
+            None => vec![Ring::new(self.points.clone())?],
+        };
+
+        // Everything within the distance of the boundary
+        let radius = distance.abs();
+        let mut pieces = Vec::new();
+        for ring in &rings {
+            for pair in ring.points().windows(2) {
+                if let Some(line) = Line::new(pair[0], pair[1]) {
+                    let left = line.shift_left(radius);
+                    let right = line.shift_right(radius);
+                    pieces.push(geo::MultiPolygon(vec![to_geo(&vec![
+                        left.pt1(),
+                        left.pt2(),
+                        right.pt2(),
+                        right.pt1(),
+                        left.pt1(),
+                    ])]));
+                }
+                let corner = Circle::new(pair[0], radius).to_ring().into_points();
+                pieces.push(geo::MultiPolygon(vec![to_geo(&corner)]));
+            }
+        }
+        let band = union_pieces(pieces);
+
+        let shape = geo::MultiPolygon(vec![self.clone().into()]);
+        let result = if distance > Distance::ZERO {
+            shape.union(&band)
+        } else {
+            shape.difference(&band)
+        };
+        Ok(from_multi(result))
+    }
+
     pub fn polylabel(&self) -> Pt2D {
         let pt = polylabel::polylabel(&to_geo(&self.points()), &1.0).unwrap();
         Pt2D::new(pt.x(), pt.y())
@@ -505,6 +549,25 @@ impl From<Polygon> for geo::Polygon<f64> {
     }
 }
 
+// The pieces overlap, so they can't just go in one MultiPolygon. Unioning them pairwise keeps each
+// union small, instead of growing one shape that every later piece is unioned against.
+fn union_pieces(mut pieces: Vec<geo::MultiPolygon<f64>>) -> geo::MultiPolygon<f64> {
+    while pieces.len() > 1 {
+        let mut merged = Vec::new();
+        let mut iter = pieces.into_iter();
+        while let Some(a) = iter.next() {
+            merged.push(match iter.next() {
+                Some(b) => a.union(&b),
+                None => a,
+            });
+        }
+        pieces = merged;
+    }
+    pieces
+        .pop()
+        .unwrap_or_else(|| geo::MultiPolygon(Vec::new()))
+}
+
 fn from_multi(multi: geo::MultiPolygon<f64>) -> Vec<Polygon> {
     multi.into_iter().map(Polygon::from).collect()
 }
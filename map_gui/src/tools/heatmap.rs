@@ -36,6 +36,16 @@ impl HeatmapOptions {
         }
     }
 
+    /// In meters
+    pub fn resolution(&self) -> usize {
+        self.resolution
+    }
+
+    /// Clamped to what the controls allow
+    pub fn set_resolution(&mut self, meters: usize) {
+        self.resolution = meters.max(1).min(100);
+    }
+
     pub fn to_controls(&self, ctx: &mut EventCtx, legend: Widget) -> Vec<Widget> {
         vec![
             // TODO Display the value...
@@ -68,7 +68,11 @@ impl<A: AppLike + 'static> State<A> for Picker<A> {
                             data_packs.runtime.insert(city);
                         }
                     }
-                    abstutil::write_json(abstutil::path("player/data.json"), &data_packs);
+                    abstutil::write_json_with(
+                        abstutil::path("player/data.json"),
+                        &data_packs,
+                        abstutil::JsonFormat::Pretty,
+                    );
 
                     let messages = ctx.loading_screen("sync files", |_, timer| sync(timer));
                     return Transition::Multi(vec![
@@ -166,9 +166,10 @@ impl MapEdits {
             return;
         }
 
-        abstutil::write_json(
+        abstutil::write_json_with(
             abstutil::path_edits(map.get_name(), &self.edits_name),
             &self.to_permanent(map),
+            abstutil::JsonFormat::Pretty,
         );
     }
 
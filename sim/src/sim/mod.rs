@@ -8,7 +8,9 @@ use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
 
-use abstutil::{prettyprint_usize, serialized_size_bytes, CmdArgs, MapName, Timer};
+use abstutil::{
+    prettyprint_usize, serialized_size_bytes, CmdArgs, MapName, SaveTransaction, Timer,
+};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
     BuildingID, BusRoute, IntersectionID, LaneID, Map, ParkingLotID, Path, PathConstraints,
@@ -814,7 +816,15 @@ impl Sim {
         }
 
         let path = self.save_path(self.time);
-        abstutil::write_binary(path.clone(), self);
+        // Even though there's only one file, staging it means a crash mid-write won't clobber an
+        // older savestate with a partial one.
+        let mut txn = SaveTransaction::new();
+        if let Err(err) = txn
+            .write_binary(path.clone(), self)
+            .and_then(|_| txn.commit())
+        {
+            panic!("Can't save {}: {}", path, err);
+        }
 
         path
     }
@@ -139,9 +139,10 @@ fn check_proposals() -> Result<(), String> {
             Ok(perma) => {
                 let map = map_model::Map::new(perma.map_name.path(), &mut timer);
                 if let Err(err) = perma.clone().to_edits(&map) {
-                    abstutil::write_json(
+                    abstutil::write_json_with(
                         "repair_attempt.json".to_string(),
                         &perma.to_edits_permissive(&map).to_permanent(&map),
+                        abstutil::JsonFormat::Pretty,
                     );
                     return Err(format!("{} is out-of-date: {}", name, err));
                 }
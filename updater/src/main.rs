@@ -6,7 +6,7 @@ use std::process::Command;
 
 use walkdir::WalkDir;
 
-use abstutil::{prettyprint_usize, CmdArgs, DataPacks, Entry, Manifest, Timer};
+use abstutil::{prettyprint_usize, CmdArgs, DataPacks, Entry, JsonFormat, Manifest, Timer};
 use geom::Percent;
 
 const MD5_BUF_READ_SIZE: usize = 4096;
@@ -129,8 +129,13 @@ fn upload(version: String) {
         }
     }
 
-    abstutil::write_json(format!("{}/MANIFEST.json", remote_base), &local);
-    abstutil::write_json("data/MANIFEST.json".to_string(), &local);
+    // Checked into git, so keep diffs readable
+    abstutil::write_json_with(
+        format!("{}/MANIFEST.json", remote_base),
+        &local,
+        JsonFormat::Pretty,
+    );
+    abstutil::write_json_with("data/MANIFEST.json".to_string(), &local, JsonFormat::Pretty);
 
     must_run_cmd(
         Command::new("aws")
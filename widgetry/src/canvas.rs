@@ -47,6 +47,8 @@ pub struct Canvas {
 
     // Kind of just widgetry state awkwardly stuck here...
     pub(crate) keys_held: HashSet<Key>,
+    // Toggled by Ctrl+F9. Outlines every member of every row and column.
+    pub(crate) debug_layout: bool,
 }
 
 impl Canvas {
@@ -75,6 +77,7 @@ impl Canvas {
             covered_areas: RefCell::new(Vec::new()),
 
             keys_held: HashSet::new(),
+            debug_layout: false,
         }
     }
 
@@ -46,6 +46,9 @@ pub(crate) use crate::widgets::button::Button;
 pub use crate::widgets::button::{Btn, MultiButton};
 pub use crate::widgets::checkbox::Checkbox;
 pub use crate::widgets::compare_times::CompareTimes;
+pub use crate::widgets::containers::{
+    AccessNode, AccessRole, Breakpoint, Container, ContainerState, WidgetPath,
+};
 pub(crate) use crate::widgets::dropdown::Dropdown;
 pub use crate::widgets::fan_chart::FanChart;
 pub use crate::widgets::filler::Filler;
@@ -58,7 +61,9 @@ pub use crate::widgets::scatter_plot::ScatterPlot;
 pub use crate::widgets::slider::Slider;
 pub use crate::widgets::spinner::Spinner;
 pub(crate) use crate::widgets::text_box::TextBox;
-pub use crate::widgets::{EdgeInsets, Outcome, Panel, Widget, WidgetImpl, WidgetOutput};
+pub use crate::widgets::{
+    EdgeInsets, LayoutSnapshot, Outcome, Panel, Widget, WidgetImpl, WidgetOutput,
+};
 
 mod app_state;
 mod assets;
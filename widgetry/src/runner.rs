@@ -12,7 +12,8 @@ use crate::app_state::App;
 use crate::assets::Assets;
 use crate::tools::screenshot::screenshot_everything;
 use crate::{
-    Canvas, Event, EventCtx, GfxCtx, Prerender, SharedAppState, Style, Text, UpdateType, UserInput,
+    Canvas, Event, EventCtx, GfxCtx, Key, Prerender, SharedAppState, Style, Text, UpdateType,
+    UserInput,
 };
 
 const UPDATE_FREQUENCY: std::time::Duration = std::time::Duration::from_millis(1000 / 30);
@@ -77,6 +78,9 @@ impl<A: SharedAppState> State<A> {
 
             if let Event::KeyPress(key) = input.event {
                 self.canvas.keys_held.insert(key);
+                if key == Key::F9 && self.canvas.keys_held.contains(&Key::LeftControl) {
+                    self.canvas.debug_layout = !self.canvas.debug_layout;
+                }
             } else if let Event::KeyRelease(key) = input.event {
                 self.canvas.keys_held.remove(&key);
             }
@@ -73,18 +73,19 @@ impl<T: 'static + Clone> Autocomplete<T> {
 }
 
 impl<T: 'static + Clone> WidgetImpl for Autocomplete<T> {
-    fn get_dims(&self) -> ScreenDims {
-        let d1 = self.tb.get_dims();
-        let d2 = self.menu.get_dims();
+    fn intrinsic_dims(&self) -> ScreenDims {
+        let d1 = self.tb.intrinsic_dims();
+        let d2 = self.menu.intrinsic_dims();
         ScreenDims::new(d1.width.max(d2.width), d1.height + d2.height)
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
-        self.tb.set_pos(top_left);
-        self.menu.set_pos(ScreenPt::new(
-            top_left.x,
-            top_left.y + self.tb.get_dims().height,
-        ));
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
+        let tb_dims = self.tb.intrinsic_dims();
+        self.tb.set_pos(top_left, tb_dims);
+        self.menu.set_pos(
+            ScreenPt::new(top_left.x, top_left.y + tb_dims.height),
+            self.menu.intrinsic_dims(),
+        );
     }
 
     fn event(&mut self, ctx: &mut EventCtx, output: &mut WidgetOutput) {
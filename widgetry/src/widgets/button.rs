@@ -1,8 +1,9 @@
 use geom::{Distance, Polygon};
 
 use crate::{
-    svg, Color, Drawable, EdgeInsets, EventCtx, GeomBatch, GfxCtx, Key, Line, MultiKey, Outcome,
-    RewriteColor, ScreenDims, ScreenPt, ScreenRectangle, Text, Widget, WidgetImpl, WidgetOutput,
+    svg, AccessRole, Color, Drawable, EdgeInsets, EventCtx, GeomBatch, GfxCtx, Key, Line, MultiKey,
+    Outcome, RewriteColor, ScreenDims, ScreenPt, ScreenRectangle, Text, Widget, WidgetImpl,
+    WidgetOutput,
 };
 
 pub struct Button {
@@ -19,6 +20,8 @@ pub struct Button {
     hitbox: Polygon,
 
     pub(crate) hovering: bool,
+    // Keyboard focus, from tabbing through a container
+    pub(crate) focused: bool,
 
     pub(crate) top_left: ScreenPt,
     pub(crate) dims: ScreenDims,
@@ -52,6 +55,7 @@ impl Button {
             hitbox,
 
             hovering: false,
+            focused: false,
 
             top_left: ScreenPt::new(0.0, 0.0),
             dims,
@@ -61,11 +65,11 @@ impl Button {
 }
 
 impl WidgetImpl for Button {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
@@ -92,20 +96,38 @@ impl WidgetImpl for Button {
             return;
         }
 
+        if self.focused && (ctx.input.pressed(Key::Enter) || ctx.input.pressed(Key::Space)) {
+            output.outcome = Outcome::Clicked(self.action.clone());
+            return;
+        }
+
         if self.hovering {
             ctx.cursor_clickable();
         }
     }
 
+    fn can_focus(&self) -> bool {
+        true
+    }
+
+    fn set_focus(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
     fn draw(&self, g: &mut GfxCtx) {
-        if self.hovering {
+        if self.hovering || self.focused {
             g.redraw_at(self.top_left, &self.draw_hovered);
-            if !self.tooltip.is_empty() {
-                g.draw_mouse_tooltip(self.tooltip.clone());
-            }
         } else {
             g.redraw_at(self.top_left, &self.draw_normal);
         }
+        // The tooltip follows the mouse, so it'd be in the wrong place for keyboard focus
+        if self.hovering && !self.tooltip.is_empty() {
+            g.draw_mouse_tooltip(self.tooltip.clone());
+        }
+    }
+
+    fn access_role(&self) -> Option<AccessRole> {
+        Some(AccessRole::Button)
     }
 }
 
@@ -536,11 +558,11 @@ impl MultiButton {
 }
 
 impl WidgetImpl for MultiButton {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
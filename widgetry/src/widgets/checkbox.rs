@@ -1,6 +1,6 @@
 use crate::{
-    Btn, Button, Color, EventCtx, GeomBatch, GfxCtx, Line, MultiKey, Outcome, RewriteColor,
-    ScreenDims, ScreenPt, Text, TextExt, TextSpan, Widget, WidgetImpl, WidgetOutput,
+    AccessRole, Btn, Button, Color, EventCtx, GeomBatch, GfxCtx, Line, MultiKey, Outcome,
+    RewriteColor, ScreenDims, ScreenPt, Text, TextExt, TextSpan, Widget, WidgetImpl, WidgetOutput,
 };
 
 pub struct Checkbox {
@@ -193,13 +193,23 @@ impl Checkbox {
     }
 }
 
+impl Checkbox {
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            std::mem::swap(&mut self.btn, &mut self.other_btn);
+            self.btn.set_pos(self.other_btn.top_left, self.btn.dims);
+            self.enabled = enabled;
+        }
+    }
+}
+
 impl WidgetImpl for Checkbox {
-    fn get_dims(&self) -> ScreenDims {
-        self.btn.get_dims()
+    fn intrinsic_dims(&self) -> ScreenDims {
+        self.btn.intrinsic_dims()
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
-        self.btn.set_pos(top_left);
+    fn set_pos(&mut self, top_left: ScreenPt, allocated: ScreenDims) {
+        self.btn.set_pos(top_left, allocated);
     }
 
     fn event(&mut self, ctx: &mut EventCtx, output: &mut WidgetOutput) {
@@ -207,13 +217,26 @@ impl WidgetImpl for Checkbox {
         if let Outcome::Clicked(_) = output.outcome {
             output.outcome = Outcome::Changed;
             std::mem::swap(&mut self.btn, &mut self.other_btn);
-            self.btn.set_pos(self.other_btn.top_left);
+            self.btn.set_pos(self.other_btn.top_left, self.btn.dims);
             self.enabled = !self.enabled;
             output.redo_layout = true;
         }
     }
 
+    fn can_focus(&self) -> bool {
+        true
+    }
+
+    fn set_focus(&mut self, focused: bool) {
+        self.btn.set_focus(focused);
+        self.other_btn.set_focus(focused);
+    }
+
     fn draw(&self, g: &mut GfxCtx) {
         self.btn.draw(g);
     }
+
+    fn access_role(&self) -> Option<AccessRole> {
+        Some(AccessRole::Checkbox)
+    }
 }
@@ -133,11 +133,11 @@ impl CompareTimes {
 }
 
 impl WidgetImpl for CompareTimes {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
@@ -1,13 +1,22 @@
-use crate::{EventCtx, GfxCtx, Outcome, ScreenDims, ScreenPt, Widget, WidgetImpl, WidgetOutput};
+use std::collections::BTreeMap;
+
+use stretch::style::Dimension;
+
+use geom::{Distance, Duration, Polygon};
+
+use crate::{
+    Checkbox, Color, EventCtx, GeomBatch, GfxCtx, Key, Line, Outcome, ScreenDims, ScreenPt,
+    ScreenRectangle, Text, UpdateType, Widget, WidgetImpl, WidgetOutput,
+};
 
 pub struct Nothing {}
 
 impl WidgetImpl for Nothing {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         unreachable!()
     }
 
-    fn set_pos(&mut self, _top_left: ScreenPt) {
+    fn set_pos(&mut self, _: ScreenPt, _: ScreenDims) {
         unreachable!()
     }
 
@@ -23,31 +32,505 @@ pub struct Container {
     // false means column
     pub is_row: bool,
     pub members: Vec<Widget>,
+    pub(crate) animation: Option<MemberAnimation>,
+    // Only for rows. Members that would run past this width start a new line.
+    pub(crate) wrap_width: Option<f64>,
+    // Sorted by descending min_width. If present, this picks is_row at layout time.
+    pub(crate) breakpoints: Vec<Breakpoint>,
+    // (thickness, color) of a line drawn between members
+    pub(crate) divider: Option<(f64, Color)>,
+    pub(crate) reorder: Option<Reorder>,
+    // For modal containers, the action produced by Escape
+    pub(crate) modal: Option<String>,
+    // Indexes into the focusable widgets of this container and everything nested inside it. Only
+    // the focus root (see `focus_root`) handles Tab, so it's the only one that tracks this.
+    focus: Option<usize>,
 }
 
 impl Container {
     pub fn new(is_row: bool, mut members: Vec<Widget>) -> Container {
         members.retain(|w| !w.widget.is::<Nothing>());
-        Container { is_row, members }
+        Container {
+            is_row,
+            members,
+            animation: None,
+            wrap_width: None,
+            breakpoints: Vec::new(),
+            divider: None,
+            reorder: None,
+            modal: None,
+            focus: None,
+        }
+    }
+
+    /// Switches between a row and a column depending on the window width. See
+    /// `Widget::responsive`.
+    pub fn responsive(members: Vec<Widget>, mut breakpoints: Vec<Breakpoint>) -> Container {
+        assert!(!breakpoints.is_empty());
+        breakpoints.sort_by(|a, b| b.min_width.partial_cmp(&a.min_width).unwrap());
+        let mut c = Container::new(breakpoints[0].is_row, members);
+        c.breakpoints = breakpoints;
+        c
+    }
+
+    /// A row that wraps members onto a new line when they'd exceed `max_width`.
+    pub fn wrap(members: Vec<Widget>, max_width: f64) -> Container {
+        let mut c = Container::new(true, members);
+        c.wrap_width = Some(max_width);
+        c
+    }
+
+    /// Finds a member with the given ID, searching through nested rows and columns.
+    pub fn find(&self, id: &str) -> Option<&Widget> {
+        self.members.iter().find_map(|w| w.find(id))
+    }
+
+    /// Finds a member with the given ID, searching through nested rows and columns.
+    pub fn find_mut(&mut self, id: &str) -> Option<&mut Widget> {
+        for w in &mut self.members {
+            if let Some(found) = w.find_mut(id) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// Finds the visible member under a point, descending into nested rows and columns. Where
+    /// members overlap, the one drawn last wins.
+    pub fn member_at(&self, pt: ScreenPt) -> Option<WidgetPath> {
+        for (idx, w) in self.members.iter().enumerate().rev() {
+            if !w.is_visible() || !w.rect.contains(pt) {
+                continue;
+            }
+            let mut path = w
+                .widget
+                .downcast_ref::<Container>()
+                .and_then(|c| c.member_at(pt))
+                .unwrap_or(WidgetPath {
+                    indices: Vec::new(),
+                    id: None,
+                });
+            path.indices.insert(0, idx);
+            if path.id.is_none() {
+                path.id = w.id.clone();
+            }
+            return Some(path);
+        }
+        None
+    }
+
+    /// Records the toggles and keyboard focus of everything inside. See `ContainerState`.
+    pub fn save_state(&self, state: &mut ContainerState) {
+        save_checkboxes(&self.members, &mut state.checkboxes);
+        if let Some(idx) = self.focus {
+            let mut ids = Vec::new();
+            collect_focusable_ids(&self.members, &mut ids);
+            state.focused = ids.get(idx).cloned().flatten();
+        }
+    }
+
+    /// Applies state recorded from an older version of this container. Widgets are matched by
+    /// ID; anything that no longer exists is ignored.
+    pub fn load_state(&mut self, state: &ContainerState) {
+        load_checkboxes(&mut self.members, &state.checkboxes);
+        if let Some(ref id) = state.focused {
+            let mut focusable = Vec::new();
+            collect_focusable(&mut self.members, &mut focusable);
+            if let Some(idx) = focusable.iter().position(|w| w.id.as_ref() == Some(id)) {
+                focusable[idx].widget.set_focus(true);
+                self.focus = Some(idx);
+            }
+        }
+    }
+
+    // Some widgets focus themselves (like a clicked TextBox), so don't trust `focus` to find the
+    // only focused one.
+    fn clear_focus(&mut self) {
+        self.focus = None;
+        let mut focusable = Vec::new();
+        collect_focusable(&mut self.members, &mut focusable);
+        for w in focusable {
+            w.widget.set_focus(false);
+        }
+    }
+
+    /// Drops keyboard focus here and in everything nested inside, if it was set with Tab. Call this
+    /// after members change, since `focus` may not point at the same widget anymore.
+    pub(crate) fn forget_focus(&mut self) {
+        if self.focus.is_some() {
+            self.clear_focus();
+        }
+        for w in &mut self.members {
+            if let Some(container) = w.widget.downcast_mut::<Container>() {
+                container.forget_focus();
+            }
+        }
+    }
+
+    /// The container that Tab moves focus around in: the open modal, if there is one.
+    pub(crate) fn focus_root(&mut self) -> &mut Container {
+        let modal = self.members.iter().position(holds_modal);
+        match modal {
+            Some(idx) => self.members[idx]
+                .widget
+                .downcast_mut::<Container>()
+                .unwrap()
+                .focus_root(),
+            None => self,
+        }
+    }
+
+    /// Handles keyboard focus, after the members have had their chance at the event. That way
+    /// buttons with a Tab hotkey still work.
+    pub(crate) fn focus_event(&mut self, ctx: &mut EventCtx) {
+        if ctx.input.left_mouse_button_pressed() {
+            // The mouse takes over
+            if self.focus.is_some() {
+                self.clear_focus();
+            }
+        } else if ctx.input.pressed(Key::Tab) {
+            self.move_focus(!ctx.is_key_down(Key::LeftShift));
+        }
+    }
+
+    /// Moves keyboard focus to the next or previous focusable member, wrapping around at the ends.
+    fn move_focus(&mut self, forwards: bool) {
+        let prev = self.focus.take();
+        let mut focusable = Vec::new();
+        collect_focusable(&mut self.members, &mut focusable);
+        if focusable.is_empty() {
+            return;
+        }
+        let n = focusable.len();
+        for w in focusable.iter_mut() {
+            w.widget.set_focus(false);
+        }
+        let next = match prev {
+            None if forwards => 0,
+            None => n - 1,
+            Some(idx) if forwards => (idx + 1) % n,
+            Some(idx) => (idx % n + n - 1) % n,
+        };
+        focusable[next].widget.set_focus(true);
+        self.focus = Some(next);
+    }
+
+    /// Called when this container replaces a previous version of itself. Members are matched up
+    /// by their ID; unnamed members never animate.
+    pub(crate) fn start_animation(&mut self, ctx: &mut EventCtx, prev: &Container) {
+        let animation = match self.animation {
+            Some(ref mut a) => a,
+            None => {
+                return;
+            }
+        };
+        animation.entering.clear();
+        animation.exiting.clear();
+
+        for w in &self.members {
+            if let Some(ref id) = w.id {
+                if !prev.members.iter().any(|x| x.id.as_ref() == Some(id)) {
+                    animation.entering.push((id.clone(), Duration::ZERO));
+                }
+            }
+        }
+        for w in &prev.members {
+            if let Some(ref id) = w.id {
+                if !self.members.iter().any(|x| x.id.as_ref() == Some(id)) {
+                    animation.exiting.push((w.rect.clone(), Duration::ZERO));
+                }
+            }
+        }
+
+        if animation.is_running() {
+            ctx.request_update(UpdateType::Game);
+        }
+    }
+}
+
+/// Dragging members of a container into a new order. Set up with `Widget::reorderable`.
+pub(crate) struct Reorder {
+    name: String,
+    // The member and where the mouse went down on it, before it's moved far enough to count as a
+    // drag. Until then, a click still reaches the member.
+    pressed: Option<(usize, ScreenPt)>,
+    // The member being dragged, by its current index
+    dragging: Option<usize>,
+    // Did the order change during this drag?
+    moved: bool,
+}
+
+// How far the mouse has to move with the button held before a press becomes a drag
+const DRAG_THRESHOLD: f64 = 5.0;
+
+impl Reorder {
+    pub fn new(name: String) -> Reorder {
+        Reorder {
+            name,
+            pressed: None,
+            dragging: None,
+            moved: false,
+        }
+    }
+}
+
+/// Interaction state that's lost when a panel is rebuilt from scratch. Take it from the old panel
+/// with `Panel::container_state` and hand it to the new one with `Panel::restore_container_state`.
+/// Unlike `Panel::restore`, this doesn't need the old panel kept around.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContainerState {
+    pub scroll_offset: (f64, f64),
+    /// Checkboxes by ID. Expanding and collapsing sections is done with these.
+    pub checkboxes: BTreeMap<String, bool>,
+    /// The ID of the widget with keyboard focus
+    pub focused: Option<String>,
+}
+
+/// Where a widget is inside nested containers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WidgetPath {
+    /// The index of the member at each level, outermost first.
+    pub indices: Vec<usize>,
+    /// The ID of the innermost widget, or of the nearest named container around it.
+    pub id: Option<String>,
+}
+
+/// What a widget is, from the point of view of a screen reader.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccessRole {
+    Button,
+    Checkbox,
+    Label,
+    List,
+    Slider,
+    TextBox,
+    /// A row or column that was explicitly described
+    Group,
+}
+
+/// One widget in `Panel::accessibility_tree`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessNode {
+    pub role: AccessRole,
+    /// Set by `Widget::accessible`, or else the widget's ID. Empty if neither exists.
+    pub label: String,
+    pub bounds: ScreenRectangle,
+    pub focusable: bool,
+    /// Only groups have children.
+    pub children: Vec<AccessNode>,
+}
+
+/// One arrangement of a responsive container.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Breakpoint {
+    /// Applies when the window is at least this wide.
+    pub min_width: f64,
+    /// Otherwise a column.
+    pub is_row: bool,
+    /// Pixels between members.
+    pub spacing: usize,
+}
+
+/// The breakpoint with the biggest `min_width` that the window still satisfies, or the one with
+/// the smallest `min_width` if the window is narrower than all of them. `breakpoints` must be
+/// sorted by descending `min_width`.
+pub(crate) fn choose_breakpoint(
+    breakpoints: &[Breakpoint],
+    window_width: f64,
+) -> Option<&Breakpoint> {
+    breakpoints
+        .iter()
+        .find(|b| window_width >= b.min_width)
+        .or_else(|| breakpoints.last())
+}
+
+/// A tooltip that appears once the mouse rests over a member for a while. Set up with
+/// `Widget::with_tooltip`.
+pub(crate) struct WithTooltip {
+    txt: Text,
+    pub delay: Duration,
+    // None when the mouse isn't over the member
+    hovered_for: Option<Duration>,
+}
+
+impl WithTooltip {
+    pub fn new(txt: Text) -> WithTooltip {
+        WithTooltip {
+            txt,
+            delay: Duration::seconds(0.5),
+            hovered_for: None,
+        }
+    }
+
+    fn event(&mut self, ctx: &mut EventCtx, rect: &ScreenRectangle) {
+        let hovering = ctx
+            .canvas
+            .get_cursor_in_screen_space()
+            .map(|pt| rect.contains(pt))
+            .unwrap_or(false);
+        if !hovering {
+            self.hovered_for = None;
+            return;
+        }
+        let hovered_for = self.hovered_for.get_or_insert(Duration::ZERO);
+        if *hovered_for >= self.delay {
+            return;
+        }
+        if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+            *hovered_for += dt;
+        }
+        // Keep the clock running until the tooltip appears
+        if *hovered_for < self.delay {
+            ctx.request_update(UpdateType::Game);
+        }
+    }
+
+    fn is_showing(&self) -> bool {
+        self.hovered_for
+            .map(|elapsed| elapsed >= self.delay)
+            .unwrap_or(false)
+    }
+}
+
+/// Fades and slides members in and out when a container is rebuilt. Off by default; enable with
+/// `Widget::animate_members`.
+pub(crate) struct MemberAnimation {
+    pub duration: Duration,
+    // (member ID, time elapsed)
+    entering: Vec<(String, Duration)>,
+    // The departing members are gone by the time we find out about them, so just remember where
+    // they were and fade out a ghost.
+    exiting: Vec<(ScreenRectangle, Duration)>,
+}
+
+impl MemberAnimation {
+    pub fn new(duration: Duration) -> MemberAnimation {
+        MemberAnimation {
+            duration,
+            entering: Vec::new(),
+            exiting: Vec::new(),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        !self.entering.is_empty() || !self.exiting.is_empty()
+    }
+
+    fn advance(&mut self, dt: Duration) {
+        let duration = self.duration;
+        for (_, elapsed) in &mut self.entering {
+            *elapsed += dt;
+        }
+        self.entering.retain(|(_, elapsed)| *elapsed < duration);
+        for (_, elapsed) in &mut self.exiting {
+            *elapsed += dt;
+        }
+        self.exiting.retain(|(_, elapsed)| *elapsed < duration);
+    }
+
+    fn entering_pct(&self, id: &str) -> Option<f64> {
+        self.entering
+            .iter()
+            .find(|(x, _)| x == id)
+            .map(|(_, elapsed)| *elapsed / self.duration)
     }
 }
 
 impl WidgetImpl for Container {
-    fn get_dims(&self) -> ScreenDims {
-        // TODO This impl isn't correct, but it works for the one use case of
-        // get_width_for_forcing.
-        let mut width: f64 = 0.0;
-        for x in &self.members {
-            width = width.max(x.get_width_for_forcing());
+    fn intrinsic_dims(&self) -> ScreenDims {
+        let members: Vec<ScreenDims> = self
+            .members
+            .iter()
+            .filter(|w| w.visible || w.reserve_space)
+            .map(outer_dims)
+            .collect();
+        if let Some(max_width) = self.wrap_width {
+            return ScreenDims::new(max_width, wrapped_height(&members, max_width));
+        }
+
+        // Members line up along the main axis and share the cross axis
+        let sum = |f: fn(&ScreenDims) -> f64| members.iter().map(f).sum();
+        let max = |f: fn(&ScreenDims) -> f64| members.iter().map(f).fold(0.0, f64::max);
+        if self.is_row {
+            ScreenDims::new(sum(|d| d.width), max(|d| d.height))
+        } else {
+            ScreenDims::new(max(|d| d.width), sum(|d| d.height))
         }
-        ScreenDims::new(width, 0.0)
     }
-    fn set_pos(&mut self, _top_left: ScreenPt) {
-        unreachable!()
+    // apply_flexbox positions each member directly
+    fn set_pos(&mut self, _: ScreenPt, _: ScreenDims) {}
+
+    fn layout_kind(&self) -> String {
+        if self.wrap_width.is_some() {
+            "wrap".to_string()
+        } else if self.is_row {
+            "row".to_string()
+        } else {
+            "column".to_string()
+        }
     }
 
     fn event(&mut self, ctx: &mut EventCtx, output: &mut WidgetOutput) {
+        // While a modal is open, nothing else sees events
+        if let Some(idx) = self.members.iter().position(holds_modal) {
+            if self.focus.is_some() {
+                self.clear_focus();
+            }
+            let w = &mut self.members[idx];
+            if is_modal(w) {
+                let outside = ctx
+                    .canvas
+                    .get_cursor_in_screen_space()
+                    .map(|pt| !w.rect.contains(pt))
+                    .unwrap_or(true);
+                let mouse_event = ctx.input.left_mouse_button_pressed()
+                    || ctx.input.left_mouse_button_released()
+                    || ctx.input.get_mouse_scroll().is_some();
+                if outside && mouse_event {
+                    if !ctx.input.has_been_consumed() {
+                        ctx.input.consume_event();
+                    }
+                    return;
+                }
+            }
+            w.widget.event(ctx, output);
+            return;
+        }
+
+        if let Some(ref cancel) = self.modal {
+            if ctx.input.pressed(Key::Escape) {
+                output.outcome = Outcome::Clicked(cancel.clone());
+                return;
+            }
+        }
+
+        if let Some(ref mut animation) = self.animation {
+            if animation.is_running() {
+                if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+                    animation.advance(dt);
+                }
+                // Keep going, even if the update event went to somebody else
+                if animation.is_running() {
+                    ctx.request_update(UpdateType::Game);
+                }
+            }
+        }
+
+        if let Some(mut reorder) = self.reorder.take() {
+            let handled = self.reorder_event(ctx, output, &mut reorder);
+            self.reorder = Some(reorder);
+            if handled {
+                return;
+            }
+        }
+
         for w in &mut self.members {
+            if !w.is_visible() {
+                continue;
+            }
+            if let Some(ref mut tooltip) = w.tooltip {
+                tooltip.event(ctx, &w.rect);
+            }
             w.widget.event(ctx, output);
             if output.outcome != Outcome::Nothing {
                 return;
@@ -56,8 +539,996 @@ impl WidgetImpl for Container {
     }
 
     fn draw(&self, g: &mut GfxCtx) {
-        for w in &self.members {
+        // Anything holding an open modal goes last, so the modal winds up on top of everything
+        for w in self.members.iter().filter(|w| !holds_modal(w)) {
             w.draw(g);
         }
+        for w in self.members.iter().filter(|w| holds_modal(w)) {
+            if is_modal(w) {
+                draw_modal_backdrop(g);
+            }
+            w.draw(g);
+        }
+
+        if let Some((thickness, color)) = self.divider {
+            self.draw_dividers(g, thickness, color);
+        }
+
+        if let Some(ref animation) = self.animation {
+            if animation.is_running() {
+                self.draw_animation(g, animation);
+            }
+        }
+
+        if let Some(idx) = self.focus {
+            self.draw_focus_ring(g, idx);
+        }
+
+        if let Some(idx) = self.reorder.as_ref().and_then(|r| r.dragging) {
+            let rect = &self.members[idx].rect;
+            if let Ok(outline) =
+                Polygon::rectangle(rect.width(), rect.height()).to_outline(Distance::meters(2.0))
+            {
+                g.fork_screenspace();
+                g.draw_polygon(g.style().hotkey_color, outline.translate(rect.x1, rect.y1));
+                g.unfork();
+            }
+        }
+
+        if g.canvas.debug_layout {
+            self.draw_debug_layout(g);
+        }
+
+        for w in &self.members {
+            if let Some(ref tooltip) = w.tooltip {
+                if w.is_visible() && tooltip.is_showing() {
+                    g.draw_mouse_tooltip(tooltip.txt.clone());
+                }
+            }
+        }
+    }
+}
+
+impl Container {
+    /// Returns true if the event was part of a drag, and members shouldn't see it.
+    fn reorder_event(
+        &mut self,
+        ctx: &mut EventCtx,
+        output: &mut WidgetOutput,
+        reorder: &mut Reorder,
+    ) -> bool {
+        let cursor = ctx.canvas.get_cursor_in_screen_space();
+        if let Some(idx) = reorder.dragging {
+            if ctx.input.left_mouse_button_released() {
+                reorder.dragging = None;
+                if std::mem::replace(&mut reorder.moved, false) {
+                    output.outcome = Outcome::Reordered(
+                        reorder.name.clone(),
+                        self.members
+                            .iter()
+                            .map(|w| w.id.clone().unwrap_or_default())
+                            .collect(),
+                    );
+                }
+            } else if let (Some(_), Some(pt)) = (ctx.input.get_moved_mouse(), cursor) {
+                let along = if self.is_row { pt.x } else { pt.y };
+                if let Some(new_idx) = self.move_member(idx, along) {
+                    reorder.dragging = Some(new_idx);
+                    reorder.moved = true;
+                    output.redo_layout = true;
+                }
+            }
+            return true;
+        }
+
+        if ctx.input.left_mouse_button_pressed() {
+            reorder.pressed = cursor.and_then(|pt| {
+                self.members
+                    .iter()
+                    .position(|w| w.is_visible() && !w.pinned && w.rect.contains(pt))
+                    .map(|idx| (idx, pt))
+            });
+        } else if ctx.input.left_mouse_button_released() {
+            reorder.pressed = None;
+        } else if let (Some((idx, start)), Some(pt)) = (reorder.pressed, cursor) {
+            if (pt.x - start.x).hypot(pt.y - start.y) >= DRAG_THRESHOLD {
+                reorder.pressed = None;
+                reorder.dragging = Some(idx);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Moves a member to wherever `along` (a position on the main axis) falls between the other
+    /// members. Pinned and hidden members stay where they are. Returns the member's new index, if
+    /// it moved.
+    fn move_member(&mut self, idx: usize, along: f64) -> Option<usize> {
+        let movable: Vec<usize> = (0..self.members.len())
+            .filter(|i| self.members[*i].is_visible() && !self.members[*i].pinned)
+            .collect();
+        let mut order: Vec<usize> = movable.iter().cloned().filter(|i| *i != idx).collect();
+        let middle = |w: &Widget| {
+            if self.is_row {
+                (w.rect.x1 + w.rect.x2) / 2.0
+            } else {
+                (w.rect.y1 + w.rect.y2) / 2.0
+            }
+        };
+        let before = order
+            .iter()
+            .filter(|i| middle(&self.members[**i]) < along)
+            .count();
+        order.insert(before, idx);
+        if order == movable {
+            return None;
+        }
+
+        // The member at each index comes from here
+        let mut sources: Vec<usize> = (0..self.members.len()).collect();
+        for (slot, from) in movable.iter().zip(order.iter()) {
+            sources[*slot] = *from;
+        }
+        let mut old: Vec<Option<Widget>> = self.members.drain(..).map(Some).collect();
+        self.members = sources
+            .into_iter()
+            .map(|from| old[from].take().unwrap())
+            .collect();
+        Some(movable[before])
+    }
+
+    /// Lines across the whole row or column, centered in the gap between each pair of members.
+    fn draw_dividers(&self, g: &mut GfxCtx, thickness: f64, color: Color) {
+        let rects: Vec<&ScreenRectangle> = self
+            .members
+            .iter()
+            .filter(|w| w.is_visible() && w.rect.width() > 0.0 && w.rect.height() > 0.0)
+            .map(|w| &w.rect)
+            .collect();
+        if rects.len() < 2 {
+            return;
+        }
+        let x1 = rects.iter().map(|r| r.x1).fold(std::f64::MAX, f64::min);
+        let x2 = rects.iter().map(|r| r.x2).fold(std::f64::MIN, f64::max);
+        let y1 = rects.iter().map(|r| r.y1).fold(std::f64::MAX, f64::min);
+        let y2 = rects.iter().map(|r| r.y2).fold(std::f64::MIN, f64::max);
+
+        let mut batch = GeomBatch::new();
+        for pair in rects.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if self.is_row {
+                // When a wrapped row starts a new line, there's no gap to divide
+                if b.x1 < a.x2 {
+                    continue;
+                }
+                let x = (a.x2 + b.x1) / 2.0 - thickness / 2.0;
+                batch.push(
+                    color,
+                    Polygon::rectangle(thickness, y2 - y1).translate(x, y1),
+                );
+            } else {
+                let y = (a.y2 + b.y1) / 2.0 - thickness / 2.0;
+                batch.push(
+                    color,
+                    Polygon::rectangle(x2 - x1, thickness).translate(x1, y),
+                );
+            }
+        }
+        g.fork_screenspace();
+        batch.draw(g);
+        g.unfork();
+    }
+
+    fn draw_focus_ring(&self, g: &mut GfxCtx, idx: usize) {
+        let mut rects = Vec::new();
+        collect_focusable_rects(&self.members, &mut rects);
+        if let Some(rect) = rects.get(idx) {
+            let pad = 2.0;
+            if let Ok(ring) = Polygon::rounded_rectangle(
+                rect.width() + 2.0 * pad,
+                rect.height() + 2.0 * pad,
+                Some(3.0),
+            )
+            .to_outline(Distance::meters(pad))
+            {
+                g.fork_screenspace();
+                g.draw_polygon(
+                    g.style().hotkey_color,
+                    ring.translate(rect.x1 - pad, rect.y1 - pad),
+                );
+                g.unfork();
+            }
+        }
+    }
+
+    fn draw_debug_layout(&self, g: &mut GfxCtx) {
+        let (color, label) = if self.is_row {
+            (Color::RED, "row")
+        } else {
+            (Color::BLUE, "col")
+        };
+        let mut batch = GeomBatch::new();
+        for w in &self.members {
+            let rect = &w.rect;
+            if rect.width() <= 0.0 || rect.height() <= 0.0 {
+                continue;
+            }
+            if let Ok(outline) =
+                Polygon::rectangle(rect.width(), rect.height()).to_outline(Distance::meters(1.0))
+            {
+                batch.push(color.alpha(0.8), outline.translate(rect.x1, rect.y1));
+            }
+            batch.append(
+                Text::from(
+                    Line(format!(
+                        "{} {}x{}",
+                        label,
+                        rect.width().round(),
+                        rect.height().round()
+                    ))
+                    .small()
+                    .fg(color),
+                )
+                .render_to_batch(g.prerender)
+                .scale(0.5)
+                .translate(rect.x1, rect.y1),
+            );
+        }
+        g.fork_screenspace();
+        batch.draw(g);
+        g.unfork();
+    }
+
+    fn draw_animation(&self, g: &mut GfxCtx, animation: &MemberAnimation) {
+        let bg = g.style().panel_bg;
+        let ghost = g.style().outline_color;
+        g.fork_screenspace();
+        for w in &self.members {
+            let pct = match w.id.as_ref().and_then(|id| animation.entering_pct(id)) {
+                Some(pct) => pct,
+                None => continue,
+            };
+            // Slide in from the start of the row or column, fading in as we go
+            let rect = &w.rect;
+            let (width, height) = if self.is_row {
+                (rect.width() * (1.0 - pct), rect.height())
+            } else {
+                (rect.width(), rect.height() * (1.0 - pct))
+            };
+            if width > 0.0 && height > 0.0 {
+                g.draw_polygon(
+                    bg,
+                    Polygon::rectangle(width, height).translate(rect.x2 - width, rect.y2 - height),
+                );
+            }
+            g.draw_polygon(bg.alpha(1.0 - pct), rect.to_polygon());
+        }
+        for (rect, elapsed) in &animation.exiting {
+            let pct = *elapsed / animation.duration;
+            let (dx, dy) = if self.is_row {
+                (pct * rect.width(), 0.0)
+            } else {
+                (0.0, pct * rect.height())
+            };
+            g.draw_polygon(
+                ghost.alpha(0.5 * (1.0 - pct)),
+                rect.to_polygon().translate(dx, dy),
+            );
+        }
+        g.unfork();
+    }
+}
+
+fn is_modal(w: &Widget) -> bool {
+    w.is_visible()
+        && w.widget
+            .downcast_ref::<Container>()
+            .map(|c| c.modal.is_some())
+            .unwrap_or(false)
+}
+
+// Is this a visible modal, or does it have one somewhere inside?
+fn holds_modal(w: &Widget) -> bool {
+    if !w.is_visible() {
+        return false;
+    }
+    match w.widget.downcast_ref::<Container>() {
+        Some(c) => c.modal.is_some() || c.members.iter().any(holds_modal),
+        None => false,
+    }
+}
+
+// Dims everything behind a modal, and keeps the map from reacting to the mouse
+fn draw_modal_backdrop(g: &mut GfxCtx) {
+    let window = g.canvas.get_window_dims();
+    g.canvas
+        .mark_covered_area(ScreenRectangle::top_left(ScreenPt::new(0.0, 0.0), window));
+    g.fork_screenspace();
+    g.draw_polygon(
+        Color::BLACK.alpha(0.5),
+        Polygon::rectangle(window.width, window.height),
+    );
+    g.unfork();
+}
+
+// In the same order as the members are traversed for events
+fn collect_focusable<'a>(members: &'a mut Vec<Widget>, results: &mut Vec<&'a mut Widget>) {
+    for w in members {
+        if !w.is_visible() {
+            continue;
+        }
+        if w.widget.is::<Container>() {
+            let container = w.widget.downcast_mut::<Container>().unwrap();
+            collect_focusable(&mut container.members, results);
+        } else if w.widget.can_focus() {
+            results.push(w);
+        }
+    }
+}
+
+fn collect_focusable_ids(members: &Vec<Widget>, results: &mut Vec<Option<String>>) {
+    for w in members {
+        if !w.is_visible() {
+            continue;
+        }
+        if let Some(container) = w.widget.downcast_ref::<Container>() {
+            collect_focusable_ids(&container.members, results);
+        } else if w.widget.can_focus() {
+            results.push(w.id.clone());
+        }
+    }
+}
+
+// Widgets without any role are skipped, and undescribed containers are flattened into their parent.
+fn describe_members(members: &Vec<Widget>, results: &mut Vec<AccessNode>) {
+    for w in members {
+        if !w.is_visible() {
+            continue;
+        }
+        let explicit = w.access.clone();
+        if let Some(container) = w.widget.downcast_ref::<Container>() {
+            if let Some((role, label)) = explicit {
+                let mut children = Vec::new();
+                describe_members(&container.members, &mut children);
+                results.push(AccessNode {
+                    role,
+                    label,
+                    bounds: w.rect.clone(),
+                    focusable: false,
+                    children,
+                });
+            } else {
+                describe_members(&container.members, results);
+            }
+            continue;
+        }
+        let (role, label) = match explicit {
+            Some(pair) => pair,
+            None => match w.widget.access_role() {
+                Some(role) => (role, w.id.clone().unwrap_or_else(String::new)),
+                None => continue,
+            },
+        };
+        results.push(AccessNode {
+            role,
+            label,
+            bounds: w.rect.clone(),
+            focusable: w.widget.can_focus(),
+            children: Vec::new(),
+        });
+    }
+}
+
+fn save_checkboxes(members: &Vec<Widget>, results: &mut BTreeMap<String, bool>) {
+    for w in members {
+        if let Some(container) = w.widget.downcast_ref::<Container>() {
+            save_checkboxes(&container.members, results);
+        } else if let (Some(cb), Some(id)) = (w.widget.downcast_ref::<Checkbox>(), &w.id) {
+            results.insert(id.clone(), cb.enabled);
+        }
+    }
+}
+
+fn load_checkboxes(members: &mut Vec<Widget>, saved: &BTreeMap<String, bool>) {
+    for w in members {
+        if let Some(container) = w.widget.downcast_mut::<Container>() {
+            load_checkboxes(&mut container.members, saved);
+        } else if let Some(enabled) = w.id.as_ref().and_then(|id| saved.get(id)) {
+            if let Some(cb) = w.widget.downcast_mut::<Checkbox>() {
+                cb.set_enabled(*enabled);
+            }
+        }
+    }
+}
+
+fn collect_focusable_rects(members: &Vec<Widget>, results: &mut Vec<ScreenRectangle>) {
+    for w in members {
+        if !w.is_visible() {
+            continue;
+        }
+        if let Some(container) = w.widget.downcast_ref::<Container>() {
+            collect_focusable_rects(&container.members, results);
+        } else if w.widget.can_focus() {
+            results.push(w.rect.clone());
+        }
+    }
+}
+
+// Includes fixed margins, since those count when deciding where a line breaks.
+fn outer_dims(w: &Widget) -> ScreenDims {
+    let dims = w.widget.intrinsic_dims();
+    let margin = &w.layout.style.margin;
+    let points = |d: Dimension| match d {
+        Dimension::Points(x) => x as f64,
+        _ => 0.0,
+    };
+    ScreenDims::new(
+        dims.width + points(margin.start) + points(margin.end),
+        dims.height + points(margin.top) + points(margin.bottom),
+    )
+}
+
+/// Places members left to right, starting a new line whenever the next one wouldn't fit, and
+/// returns the total height of all the lines. A member wider than `max_width` gets a line to
+/// itself.
+fn wrapped_height(members: &[ScreenDims], max_width: f64) -> f64 {
+    let mut total_height = 0.0;
+    let mut line_width = 0.0;
+    let mut line_height: f64 = 0.0;
+    for dims in members {
+        if line_width > 0.0 && line_width + dims.width > max_width {
+            total_height += line_height;
+            line_width = 0.0;
+            line_height = 0.0;
+        }
+        line_width += dims.width;
+        line_height = line_height.max(dims.height);
+    }
+    total_height + line_height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Dummy;
+
+    impl WidgetImpl for Dummy {
+        fn intrinsic_dims(&self) -> ScreenDims {
+            ScreenDims::new(10.0, 10.0)
+        }
+        fn set_pos(&mut self, _: ScreenPt, _: ScreenDims) {}
+        fn event(&mut self, _: &mut EventCtx, _: &mut WidgetOutput) {}
+        fn draw(&self, _: &mut GfxCtx) {}
+    }
+
+    struct Fixed(f64);
+
+    impl WidgetImpl for Fixed {
+        fn intrinsic_dims(&self) -> ScreenDims {
+            ScreenDims::new(self.0, 10.0)
+        }
+        fn set_pos(&mut self, _: ScreenPt, _: ScreenDims) {}
+        fn event(&mut self, _: &mut EventCtx, _: &mut WidgetOutput) {}
+        fn draw(&self, _: &mut GfxCtx) {}
+    }
+
+    // Lays out members in a row forced to `width`, returning each member's resulting width
+    fn squeeze(members: Vec<Widget>, width: f64) -> Vec<f64> {
+        use stretch::geometry::Size;
+        use stretch::node::Stretch;
+        use stretch::number::Number;
+        use stretch::style::Style;
+
+        let row = Widget::custom_row(members).force_width(width);
+        let mut stretch = Stretch::new();
+        let root = stretch.new_node(Style::default(), Vec::new()).unwrap();
+        let mut nodes = Vec::new();
+        row.get_flexbox(root, &mut stretch, &mut nodes);
+        let container_size = Size {
+            width: Number::Undefined,
+            height: Number::Undefined,
+        };
+        stretch.compute_layout(root, container_size).unwrap();
+        // The first node is the row itself
+        nodes[1..]
+            .iter()
+            .map(|n| stretch.layout(*n).unwrap().size.width as f64)
+            .collect()
+    }
+
+    // Lays out one member in a row forced to `width`, returning the member's resulting size
+    fn lay_out_in_row(member: Widget, width: f64) -> (f64, f64) {
+        use stretch::node::Stretch;
+        use stretch::style::Style;
+
+        let row = Widget::custom_row(vec![member]).force_width(width);
+        let mut stretch = Stretch::new();
+        let root = stretch.new_node(Style::default(), Vec::new()).unwrap();
+        let mut nodes = Vec::new();
+        row.get_flexbox(root, &mut stretch, &mut nodes);
+        row.compute_layout(&mut stretch, root, &nodes);
+        let size = stretch.layout(nodes[1]).unwrap().size;
+        (size.width as f64, size.height as f64)
+    }
+
+    #[test]
+    fn aspect_ratio_sizes_the_cross_axis() {
+        use geom::Percent;
+
+        let fixed = |w| Widget::new(Box::new(Fixed(w)));
+        assert_eq!(lay_out_in_row(fixed(60.0), 100.0), (60.0, 10.0));
+        assert_eq!(
+            lay_out_in_row(fixed(60.0).aspect_ratio(2.0), 100.0),
+            (60.0, 30.0)
+        );
+        // The ratio follows the share of the row the member gets
+        assert_eq!(
+            lay_out_in_row(
+                fixed(60.0)
+                    .pct_of_parent(Percent::int(50))
+                    .aspect_ratio(2.0),
+                100.0
+            ),
+            (50.0, 25.0)
+        );
+        // And it gives way to hard limits
+        assert_eq!(
+            lay_out_in_row(fixed(60.0).aspect_ratio(2.0).min_height(40.0), 100.0),
+            (60.0, 40.0)
+        );
+    }
+
+    // Like Panel::recompute_layout, but without an EventCtx: sets every rect from the flexbox
+    fn lay_out(widget: &mut Widget) {
+        use stretch::node::Stretch;
+        use stretch::style::Style;
+
+        let mut stretch = Stretch::new();
+        let root = stretch.new_node(Style::default(), Vec::new()).unwrap();
+        let mut nodes = Vec::new();
+        widget.get_flexbox(root, &mut stretch, &mut nodes);
+        widget.compute_layout(&mut stretch, root, &nodes);
+        place(widget, &stretch, &nodes, &mut 0, 0.0, 0.0);
+    }
+
+    // Walks the nodes in the same order as get_flexbox. Locations are relative to the parent.
+    fn place(
+        widget: &mut Widget,
+        stretch: &stretch::node::Stretch,
+        nodes: &[stretch::node::Node],
+        next: &mut usize,
+        dx: f64,
+        dy: f64,
+    ) {
+        let layout = stretch.layout(nodes[*next]).unwrap();
+        *next += 1;
+        let x = dx + layout.location.x as f64;
+        let y = dy + layout.location.y as f64;
+        widget.rect = ScreenRectangle::top_left(
+            ScreenPt::new(x, y),
+            ScreenDims::new(layout.size.width as f64, layout.size.height as f64),
+        );
+        if let Some(container) = widget.widget.downcast_mut::<Container>() {
+            for member in &mut container.members {
+                place(member, stretch, nodes, next, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn layout_snapshots_describe_the_tree() {
+        use crate::LayoutSnapshot;
+
+        let fixed = |w, name| Widget::new(Box::new(Fixed(w))).named(name);
+        let mut panel = Widget::custom_col(vec![
+            fixed(30.0, "title"),
+            Widget::custom_row(vec![fixed(20.0, "a"), fixed(40.0, "b")]).named("buttons"),
+        ]);
+        lay_out(&mut panel);
+
+        let snapshot = |kind: &str,
+                        id: Option<&str>,
+                        x: f64,
+                        y: f64,
+                        width: f64,
+                        members: Vec<LayoutSnapshot>| LayoutSnapshot {
+            kind: kind.to_string(),
+            id: id.map(|id| id.to_string()),
+            x,
+            y,
+            width,
+            height: 10.0,
+            members,
+        };
+        let mut expected = snapshot(
+            "column",
+            None,
+            0.0,
+            0.0,
+            60.0,
+            vec![
+                snapshot("Fixed", Some("title"), 0.0, 0.0, 30.0, Vec::new()),
+                snapshot(
+                    "row",
+                    Some("buttons"),
+                    0.0,
+                    10.0,
+                    60.0,
+                    vec![
+                        snapshot("Fixed", Some("a"), 0.0, 10.0, 20.0, Vec::new()),
+                        snapshot("Fixed", Some("b"), 20.0, 10.0, 40.0, Vec::new()),
+                    ],
+                ),
+            ],
+        );
+        expected.height = 20.0;
+        assert_eq!(panel.layout_snapshot(), expected);
+    }
+
+    fn assert_widths(actual: Vec<f64>, expected: Vec<f64>) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!(
+                (a - e).abs() < 0.01,
+                "got {:?}, expected {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn overfull_rows_shrink_by_factor() {
+        let fixed = |w| Widget::new(Box::new(Fixed(w)));
+        // 40 pixels too wide. The first gives up a quarter of that, the last the rest.
+        assert_widths(
+            squeeze(
+                vec![
+                    fixed(60.0),
+                    fixed(60.0).flex_shrink(0.0),
+                    fixed(60.0).flex_shrink(3.0),
+                ],
+                140.0,
+            ),
+            vec![50.0, 60.0, 30.0],
+        );
+    }
+
+    #[test]
+    fn overfull_rows_respect_min_size() {
+        let fixed = |w| Widget::new(Box::new(Fixed(w)));
+        // Sharing the 80 pixel overflow evenly would leave the middle one at 33, so it stops at its
+        // minimum and the others make up the difference.
+        assert_widths(
+            squeeze(
+                vec![fixed(60.0), fixed(60.0).min_width(50.0), fixed(60.0)],
+                100.0,
+            ),
+            vec![25.0, 50.0, 25.0],
+        );
+    }
+
+    #[test]
+    fn members_can_take_a_share_of_the_row() {
+        use geom::Percent;
+
+        let fixed = |w| Widget::new(Box::new(Fixed(w)));
+        assert_widths(
+            squeeze(
+                vec![
+                    fixed(20.0).pct_of_parent(Percent::int(30)),
+                    fixed(20.0),
+                    fixed(20.0).pct_of_parent(Percent::int(50)),
+                ],
+                200.0,
+            ),
+            vec![60.0, 20.0, 100.0],
+        );
+        // Asking for 120% in total gets scaled down
+        assert_widths(
+            squeeze(
+                vec![
+                    fixed(10.0).pct_of_parent(Percent::int(80)),
+                    fixed(10.0).pct_of_parent(Percent::int(40)),
+                ],
+                120.0,
+            ),
+            vec![80.0, 40.0],
+        );
+    }
+
+    #[test]
+    fn containers_measure_their_members() {
+        let fixed = |w| Widget::new(Box::new(Fixed(w)));
+        let col = Widget::custom_col(vec![
+            Widget::custom_row(vec![fixed(30.0), fixed(20.0).margin_right(5)]),
+            fixed(40.0),
+            fixed(100.0).hide(),
+        ]);
+        let dims = col.widget.intrinsic_dims();
+        assert_widths(vec![dims.width, dims.height], vec![55.0, 20.0]);
+    }
+
+    #[test]
+    fn dragged_members_move_around_pinned_ones() {
+        let member = |name: &str, x: f64| {
+            placed(Widget::new(Box::new(Dummy)).named(name), x, 0.0, 10.0, 10.0)
+        };
+        let mut row = Container::new(
+            true,
+            vec![
+                member("a", 0.0),
+                member("header", 10.0).pinned(),
+                member("b", 20.0),
+                member("c", 30.0),
+            ],
+        );
+        let names = |c: &Container| -> Vec<String> {
+            c.members.iter().map(|w| w.id.clone().unwrap()).collect()
+        };
+
+        // Dropping "a" just past the middle of "b" swaps them, skipping over the header
+        assert_eq!(row.move_member(0, 26.0), Some(2));
+        assert_eq!(names(&row), vec!["b", "header", "a", "c"]);
+        // Not past the middle of anything new, so nothing changes
+        assert_eq!(row.move_member(2, 22.0), None);
+    }
+
+    struct Focusable(bool);
+
+    impl WidgetImpl for Focusable {
+        fn intrinsic_dims(&self) -> ScreenDims {
+            ScreenDims::new(10.0, 10.0)
+        }
+        fn set_pos(&mut self, _: ScreenPt, _: ScreenDims) {}
+        fn event(&mut self, _: &mut EventCtx, _: &mut WidgetOutput) {}
+        fn draw(&self, _: &mut GfxCtx) {}
+        fn can_focus(&self) -> bool {
+            true
+        }
+        fn set_focus(&mut self, focused: bool) {
+            self.0 = focused;
+        }
+    }
+
+    #[test]
+    fn focus_survives_a_rebuild() {
+        let build = |names: Vec<&str>| {
+            Container::new(
+                false,
+                names
+                    .into_iter()
+                    .map(|n| Widget::new(Box::new(Focusable(false))).named(n))
+                    .collect(),
+            )
+        };
+        let mut old = build(vec!["a", "b", "c"]);
+        old.focus = Some(1);
+        let mut state = ContainerState::default();
+        old.save_state(&mut state);
+        assert_eq!(state.focused, Some("b".to_string()));
+
+        // The rebuilt version has something new before the focused widget
+        let mut new = build(vec!["new", "a", "b", "c"]);
+        new.load_state(&state);
+        assert_eq!(new.focus, Some(2));
+        assert!(new.members[2].widget.downcast_ref::<Focusable>().unwrap().0);
+    }
+
+    #[test]
+    fn only_one_member_has_focus() {
+        let focused = |c: &Container| {
+            c.members
+                .iter()
+                .map(|w| w.widget.downcast_ref::<Focusable>().unwrap().0)
+                .collect::<Vec<_>>()
+        };
+        let mut column = Container::new(
+            false,
+            vec![
+                Widget::new(Box::new(Focusable(false))),
+                Widget::new(Box::new(Focusable(false))),
+                // Focused itself, like a clicked TextBox
+                Widget::new(Box::new(Focusable(true))),
+            ],
+        );
+        column.move_focus(true);
+        assert_eq!(focused(&column), vec![true, false, false]);
+
+        // Pretend a member was replaced; the old index shouldn't be trusted
+        column.forget_focus();
+        assert_eq!(column.focus, None);
+        assert_eq!(focused(&column), vec![false, false, false]);
+    }
+
+    #[test]
+    fn modals_are_found_inside_other_containers() {
+        let column = |members: Vec<Widget>| Widget::new(Box::new(Container::new(false, members)));
+        let focusable = || Widget::new(Box::new(Focusable(false)));
+
+        let dialog = column(vec![focusable(), focusable()]).modal("cancel");
+        assert!(is_modal(&dialog));
+        let panel = column(vec![focusable(), column(vec![focusable(), dialog])]);
+        assert!(holds_modal(&panel));
+        assert!(!holds_modal(
+            &panel.widget.downcast_ref::<Container>().unwrap().members[0]
+        ));
+
+        // A hidden modal doesn't trap anything
+        let hidden = column(vec![focusable()]).modal("cancel").hide();
+        assert!(!holds_modal(&column(vec![focusable(), hidden])));
+    }
+
+    fn placed(widget: Widget, x: f64, y: f64, width: f64, height: f64) -> Widget {
+        let mut widget = widget;
+        widget.rect =
+            ScreenRectangle::top_left(ScreenPt::new(x, y), ScreenDims::new(width, height));
+        widget
+    }
+
+    #[test]
+    fn member_at_descends_and_prefers_the_topmost() {
+        let inner = Container::new(
+            false,
+            vec![
+                placed(Widget::new(Box::new(Dummy)), 0.0, 0.0, 10.0, 10.0),
+                placed(
+                    Widget::new(Box::new(Dummy)).named("b"),
+                    0.0,
+                    10.0,
+                    10.0,
+                    10.0,
+                ),
+            ],
+        );
+        let outer = Container::new(
+            true,
+            vec![
+                placed(
+                    Widget::new(Box::new(inner)).named("col"),
+                    0.0,
+                    0.0,
+                    10.0,
+                    20.0,
+                ),
+                placed(
+                    Widget::new(Box::new(Dummy)).named("under"),
+                    20.0,
+                    0.0,
+                    10.0,
+                    10.0,
+                ),
+                placed(
+                    Widget::new(Box::new(Dummy)).named("over"),
+                    25.0,
+                    0.0,
+                    10.0,
+                    10.0,
+                ),
+                placed(
+                    Widget::new(Box::new(Dummy)).named("hidden").hide(),
+                    50.0,
+                    0.0,
+                    10.0,
+                    10.0,
+                ),
+            ],
+        );
+
+        let at = |x, y| outer.member_at(ScreenPt::new(x, y));
+        assert_eq!(
+            at(5.0, 15.0),
+            Some(WidgetPath {
+                indices: vec![0, 1],
+                id: Some("b".to_string()),
+            })
+        );
+        // Unnamed members take the ID of the container around them
+        assert_eq!(
+            at(5.0, 5.0),
+            Some(WidgetPath {
+                indices: vec![0, 0],
+                id: Some("col".to_string()),
+            })
+        );
+        assert_eq!(at(27.0, 5.0).unwrap().id, Some("over".to_string()));
+        assert_eq!(at(55.0, 5.0), None);
+        assert_eq!(at(100.0, 100.0), None);
+    }
+
+    #[test]
+    fn exact_fit() {
+        let members = vec![ScreenDims::new(30.0, 10.0); 3];
+        assert_eq!(wrapped_height(&members, 90.0), 10.0);
+    }
+
+    #[test]
+    fn one_over_the_edge() {
+        let members = vec![
+            ScreenDims::new(30.0, 10.0),
+            ScreenDims::new(30.0, 10.0),
+            ScreenDims::new(31.0, 20.0),
+        ];
+        assert_eq!(wrapped_height(&members, 90.0), 30.0);
+    }
+
+    #[test]
+    fn narrowest_breakpoint_is_the_fallback() {
+        let breakpoints = vec![
+            Breakpoint {
+                min_width: 800.0,
+                is_row: true,
+                spacing: 10,
+            },
+            Breakpoint {
+                min_width: 400.0,
+                is_row: false,
+                spacing: 5,
+            },
+        ];
+        assert_eq!(
+            choose_breakpoint(&breakpoints, 1000.0),
+            Some(&breakpoints[0])
+        );
+        assert_eq!(
+            choose_breakpoint(&breakpoints, 800.0),
+            Some(&breakpoints[0])
+        );
+        assert_eq!(
+            choose_breakpoint(&breakpoints, 600.0),
+            Some(&breakpoints[1])
+        );
+        assert_eq!(
+            choose_breakpoint(&breakpoints, 100.0),
+            Some(&breakpoints[1])
+        );
+    }
+
+    #[test]
+    fn too_wide_for_any_line() {
+        let members = vec![ScreenDims::new(100.0, 10.0), ScreenDims::new(100.0, 10.0)];
+        assert_eq!(wrapped_height(&members, 90.0), 20.0);
+    }
+
+    #[test]
+    fn accessibility_tree_flattens_undescribed_containers() {
+        let row = Container::new(
+            true,
+            vec![
+                Widget::new(Box::new(Focusable(false))).accessible(AccessRole::Button, "ok"),
+                // Has no role of its own, so it's left out
+                Widget::new(Box::new(Dummy)).named("decoration"),
+                Widget::new(Box::new(Focusable(false)))
+                    .accessible(AccessRole::Button, "hidden")
+                    .hide(),
+            ],
+        );
+        let group = Container::new(
+            false,
+            vec![Widget::new(Box::new(Dummy)).accessible(AccessRole::Label, "inside")],
+        );
+        let outer = Container::new(
+            false,
+            vec![
+                Widget::new(Box::new(row)),
+                Widget::new(Box::new(group)).accessible(AccessRole::Group, "details"),
+            ],
+        );
+
+        let tree = outer.accessibility_tree();
+        let summary: Vec<(AccessRole, &str, bool, usize)> = tree
+            .iter()
+            .map(|n| (n.role, n.label.as_str(), n.focusable, n.children.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (AccessRole::Button, "ok", true, 0),
+                (AccessRole::Group, "details", false, 1),
+            ]
+        );
+        assert_eq!(tree[1].children[0].label, "inside");
+        assert!(!tree[1].children[0].focusable);
     }
 }
@@ -1,8 +1,8 @@
 use geom::{Distance, Polygon, Pt2D};
 
 use crate::{
-    Btn, Button, Choice, Color, EventCtx, GeomBatch, GfxCtx, Menu, Outcome, ScreenDims, ScreenPt,
-    ScreenRectangle, WidgetImpl, WidgetOutput,
+    AccessRole, Btn, Button, Choice, Color, EventCtx, GeomBatch, GfxCtx, Menu, Outcome, ScreenDims,
+    ScreenPt, ScreenRectangle, WidgetImpl, WidgetOutput,
 };
 
 pub struct Dropdown<T: Clone> {
@@ -67,27 +67,31 @@ impl<T: 'static + Clone> Dropdown<T> {
         )
         .take_menu();
         let y1_below = self.btn.top_left.y + self.btn.dims.height + 15.0;
-
-        menu.set_pos(ScreenPt::new(
-            self.btn.top_left.x,
-            // top_left_for_corner doesn't quite work
-            if y1_below + menu.get_dims().height < ctx.canvas.window_height {
-                y1_below
-            } else {
-                self.btn.top_left.y - 15.0 - menu.get_dims().height
-            },
-        ));
+        let dims = menu.intrinsic_dims();
+
+        menu.set_pos(
+            ScreenPt::new(
+                self.btn.top_left.x,
+                // top_left_for_corner doesn't quite work
+                if y1_below + dims.height < ctx.canvas.window_height {
+                    y1_below
+                } else {
+                    self.btn.top_left.y - 15.0 - dims.height
+                },
+            ),
+            dims,
+        );
         self.menu = Some(menu);
     }
 }
 
 impl<T: 'static + Clone> WidgetImpl for Dropdown<T> {
-    fn get_dims(&self) -> ScreenDims {
-        self.btn.get_dims()
+    fn intrinsic_dims(&self) -> ScreenDims {
+        self.btn.intrinsic_dims()
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
-        self.btn.set_pos(top_left);
+    fn set_pos(&mut self, top_left: ScreenPt, allocated: ScreenDims) {
+        self.btn.set_pos(top_left, allocated);
     }
 
     fn event(&mut self, ctx: &mut EventCtx, output: &mut WidgetOutput) {
@@ -98,17 +102,19 @@ impl<T: 'static + Clone> WidgetImpl for Dropdown<T> {
                 self.current_idx = self.menu.take().unwrap().take_current_choice();
                 output.outcome = Outcome::Changed;
                 let top_left = self.btn.top_left;
+                let focused = self.btn.focused;
                 self.btn = make_btn(
                     ctx,
                     &self.choices[self.current_idx].label,
                     &self.label,
                     self.blank_btn_label,
                 );
-                self.btn.set_pos(top_left);
+                self.btn.set_pos(top_left, self.btn.dims);
+                self.btn.set_focus(focused);
                 output.redo_layout = true;
             } else if ctx.normal_left_click() {
                 if let Some(pt) = ctx.canvas.get_cursor_in_screen_space() {
-                    if !ScreenRectangle::top_left(m.top_left, m.get_dims()).contains(pt) {
+                    if !ScreenRectangle::top_left(m.top_left, m.intrinsic_dims()).contains(pt) {
                         self.menu = None;
                     }
                 } else {
@@ -127,14 +133,22 @@ impl<T: 'static + Clone> WidgetImpl for Dropdown<T> {
         }
     }
 
+    fn can_focus(&self) -> bool {
+        true
+    }
+
+    fn set_focus(&mut self, focused: bool) {
+        self.btn.set_focus(focused);
+    }
+
     fn draw(&self, g: &mut GfxCtx) {
         self.btn.draw(g);
         if let Some(ref m) = self.menu {
             // We need a background too! Add some padding and an outline.
             // TODO Little embedded Panel could make more sense?
             let pad = 5.0;
-            let width = m.get_dims().width + 2.0 * pad;
-            let height = m.get_dims().height + 2.0 * pad;
+            let width = m.intrinsic_dims().width + 2.0 * pad;
+            let height = m.intrinsic_dims().height + 2.0 * pad;
             let rect = Polygon::rounded_rectangle(width, height, Some(5.0));
             let draw_bg = g.upload(GeomBatch::from(vec![
                 (Color::grey(0.3), rect.clone()),
@@ -156,7 +170,7 @@ impl<T: 'static + Clone> WidgetImpl for Dropdown<T> {
 
             // Dropdown menus often leak out of their Panel
             g.canvas
-                .mark_covered_area(ScreenRectangle::top_left(m.top_left, m.get_dims()));
+                .mark_covered_area(ScreenRectangle::top_left(m.top_left, m.intrinsic_dims()));
         }
     }
 
@@ -171,6 +185,10 @@ impl<T: 'static + Clone> WidgetImpl for Dropdown<T> {
             // menu.
         }
     }
+
+    fn access_role(&self) -> Option<AccessRole> {
+        Some(AccessRole::List)
+    }
 }
 
 fn make_btn(ctx: &EventCtx, label: &str, tooltip: &str, blank_btn_label: bool) -> Button {
@@ -181,11 +181,11 @@ impl FanChart {
 }
 
 impl WidgetImpl for FanChart {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
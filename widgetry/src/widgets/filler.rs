@@ -24,11 +24,11 @@ impl Filler {
 }
 
 impl WidgetImpl for Filler {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
@@ -44,11 +44,11 @@ impl JustDraw {
 }
 
 impl WidgetImpl for JustDraw {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
@@ -86,11 +86,11 @@ impl DrawWithTooltips {
 }
 
 impl WidgetImpl for DrawWithTooltips {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
@@ -138,11 +138,11 @@ impl DeferDraw {
 }
 
 impl WidgetImpl for DeferDraw {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
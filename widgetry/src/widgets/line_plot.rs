@@ -203,11 +203,11 @@ impl<T: Yvalue<T>> LinePlot<T> {
 }
 
 impl<T: Yvalue<T>> WidgetImpl for LinePlot<T> {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
@@ -1,8 +1,8 @@
 use geom::Pt2D;
 
 use crate::{
-    text, Choice, EventCtx, GfxCtx, Key, Line, Outcome, ScreenDims, ScreenPt, ScreenRectangle,
-    Style, Text, Widget, WidgetImpl, WidgetOutput,
+    text, AccessRole, Choice, EventCtx, GfxCtx, Key, Line, Outcome, ScreenDims, ScreenPt,
+    ScreenRectangle, Style, Text, Widget, WidgetImpl, WidgetOutput,
 };
 
 pub struct Menu<T> {
@@ -69,11 +69,11 @@ impl<T: 'static> Menu<T> {
 }
 
 impl<T: 'static> WidgetImpl for Menu<T> {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
@@ -190,4 +190,8 @@ impl<T: 'static> WidgetImpl for Menu<T> {
             }
         }
     }
+
+    fn access_role(&self) -> Option<AccessRole> {
+        Some(AccessRole::List)
+    }
 }
@@ -1,15 +1,20 @@
 use std::collections::HashSet;
 
+use serde::{Deserialize, Serialize};
 use stretch::geometry::{Rect, Size};
 use stretch::node::{Node, Stretch};
 use stretch::number::Number;
+use stretch::result::Layout;
 use stretch::style::{
-    AlignItems, Dimension, FlexDirection, FlexWrap, JustifyContent, PositionType, Style,
+    AlignItems, Dimension, Display, FlexDirection, FlexWrap, JustifyContent, PositionType, Style,
 };
 
-use geom::{Distance, Percent, Polygon};
+use geom::{Distance, Duration, Percent, Polygon};
 
-use crate::widgets::containers::{Container, Nothing};
+use crate::widgets::containers::{
+    choose_breakpoint, AccessRole, Breakpoint, Container, MemberAnimation, Nothing, Reorder,
+    WithTooltip,
+};
 pub use crate::widgets::panel::Panel;
 use crate::{
     Button, Checkbox, Choice, Color, DeferDraw, DrawWithTooltips, Drawable, Dropdown, EventCtx,
@@ -38,11 +43,14 @@ pub mod text_box;
 /// Create a new widget by implementing this trait. You can instantiate your widget by calling
 /// `Widget::new(Box::new(instance of your new widget))`, which gives you the usual style options.
 pub trait WidgetImpl: downcast_rs::Downcast {
-    /// What width and height does the widget occupy? If this changes, be sure to set
-    /// `redo_layout` to true in `event`.
-    fn get_dims(&self) -> ScreenDims;
-    /// Your widget's top left corner should be here. Handle mouse events and draw appropriately.
-    fn set_pos(&mut self, top_left: ScreenPt);
+    /// What width and height does the widget want, before layout decides anything? This is measured
+    /// first, then layout hands out space. If this changes, be sure to set `redo_layout` to true in
+    /// `event`.
+    fn intrinsic_dims(&self) -> ScreenDims;
+    /// Your widget's top left corner should be here, with `allocated` space. That may be more or
+    /// less than `intrinsic_dims`, when the widget is stretched or squeezed; most widgets just keep
+    /// drawing at their intrinsic size. Handle mouse events and draw appropriately.
+    fn set_pos(&mut self, top_left: ScreenPt, allocated: ScreenDims);
     /// Your chance to react to an event. Any side effects outside of this widget are communicated
     /// through the output.
     fn event(&mut self, ctx: &mut EventCtx, output: &mut WidgetOutput);
@@ -58,6 +66,40 @@ pub trait WidgetImpl: downcast_rs::Downcast {
     fn restore(&mut self, _: &mut EventCtx, _prev: &Box<dyn WidgetImpl>) {
         unreachable!()
     }
+    /// Can keyboard focus land on this widget? If so, implement `set_focus` too.
+    fn can_focus(&self) -> bool {
+        false
+    }
+    /// Keyboard focus moved onto or off of this widget. While focused, Enter or Space should
+    /// activate it.
+    fn set_focus(&mut self, _focused: bool) {
+        unreachable!()
+    }
+    /// Describes this kind of widget in a `LayoutSnapshot`. By default, the name of the type.
+    fn layout_kind(&self) -> String {
+        let name = std::any::type_name::<Self>();
+        // Drop the module path and any type parameters
+        let name = name.split('<').next().unwrap();
+        name.rsplit("::").next().unwrap().to_string()
+    }
+    /// What role does this widget play, for assistive tools reading `Panel::accessibility_tree`?
+    /// Widgets that are purely decorative can leave this as None.
+    fn access_role(&self) -> Option<AccessRole> {
+        None
+    }
+}
+
+/// The computed layout of a widget tree, for tests to compare across changes without resorting to
+/// screenshots.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutSnapshot {
+    pub kind: String,
+    pub id: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub members: Vec<LayoutSnapshot>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -67,6 +109,9 @@ pub enum Outcome {
     /// A dropdown, checkbox, spinner, etc changed values. Usually this triggers a refresh of
     /// everything, so not useful to plumb along what changed.
     Changed,
+    /// The members of a reorderable row or column were dragged into a new order. Has the name of
+    /// the container, then the IDs of its members in their new order.
+    Reordered(String, Vec<String>),
     /// Nothing happened
     Nothing,
 }
@@ -99,6 +144,29 @@ pub struct Widget {
     // to_geom forces this one to happen
     bg_batch: Option<GeomBatch>,
     id: Option<String>,
+    // Hidden widgets don't get events or draw. Unless reserve_space is set, they take no room
+    // either.
+    visible: bool,
+    reserve_space: bool,
+    // Only the container holding this widget handles this
+    tooltip: Option<WithTooltip>,
+    // Overrides the role and label the widget reports by default
+    access: Option<(AccessRole, String)>,
+    // Stays put in a reorderable container
+    pinned: bool,
+}
+
+#[derive(PartialEq)]
+enum LayoutInput {
+    Container {
+        is_row: bool,
+        members: usize,
+        visible: bool,
+    },
+    Leaf {
+        dims: ScreenDims,
+        visible: bool,
+    },
 }
 
 struct LayoutStyle {
@@ -108,6 +176,10 @@ struct LayoutStyle {
     // If None, as round as possible
     rounded_radius: Option<f64>,
     style: Style,
+    // Size along the parent's main axis, as a share of the parent
+    pct_of_parent: Option<Percent>,
+    // Width over height
+    aspect_ratio: Option<f64>,
 }
 
 // Layouting
@@ -170,10 +242,48 @@ impl Widget {
             Dimension::Points((ctx.canvas.window_width * width.inner()) as f32);
         self
     }
+    /// Take up this share of the surrounding row's width or column's height. The space is claimed
+    /// before the other members are sized, and this member won't shrink. Only works when the row
+    /// or column's own size is known, like with `fill_width` or `force_width`. If the members of
+    /// one row or column ask for more than 100% in total, they're all scaled down to fit.
+    pub fn pct_of_parent(mut self, pct: Percent) -> Widget {
+        self.layout.pct_of_parent = Some(pct);
+        self
+    }
+
+    /// Prefer this width over height. Once layout works out one side, the other is sized to match:
+    /// the height comes from the width if only the width is set (like with `fill_width` or
+    /// `force_width`), the width from the height if only the height is, and otherwise the side
+    /// across the surrounding row or column comes from the side along it. The ratio gives way to
+    /// `min_width` and friends. The widget learns the space it got in `set_pos`, so it can scale
+    /// its drawing to fit.
+    pub fn aspect_ratio(mut self, width_over_height: f64) -> Widget {
+        self.layout.aspect_ratio = Some(width_over_height);
+        self
+    }
+
+    /// When a row or column is forced smaller than its members' natural size, each member gives up
+    /// space in proportion to this factor times its natural size. The default is 1; 0 never
+    /// shrinks. Widgets with a fixed size still draw at that size, so this mainly makes sense for
+    /// nested rows and columns.
+    pub fn flex_shrink(mut self, factor: f64) -> Widget {
+        self.layout.style.flex_shrink = factor as f32;
+        self
+    }
+    /// Don't shrink narrower than this, even when the row or column around is squeezed.
+    pub fn min_width(mut self, width: f64) -> Widget {
+        self.layout.style.min_size.width = Dimension::Points(width as f32);
+        self
+    }
+    /// Don't shrink shorter than this, even when the row or column around is squeezed.
+    pub fn min_height(mut self, height: f64) -> Widget {
+        self.layout.style.min_size.height = Dimension::Points(height as f32);
+        self
+    }
 
     // Needed for force_width.
     pub fn get_width_for_forcing(&self) -> f64 {
-        self.widget.get_dims().width
+        self.widget.intrinsic_dims().width
     }
 
     pub fn bg(mut self, color: Color) -> Widget {
@@ -301,10 +411,114 @@ impl Widget {
         self
     }
 
+    /// Only for rows/columns. When a new Panel replaces an old one with `restore`, members
+    /// (matched by ID) that appear or disappear will fade and slide in or out over the given
+    /// duration. The container must be named too.
+    pub fn animate_members(mut self, duration: Duration) -> Widget {
+        if let Some(container) = self.widget.downcast_mut::<Container>() {
+            container.animation = Some(MemberAnimation::new(duration));
+        } else {
+            panic!("animate_members called on something that isn't a row or column");
+        }
+        self
+    }
+
+    /// Only for rows/columns. Draws a line between each pair of members, in the middle of the
+    /// space between them: vertical lines in a row, horizontal lines in a column. Members need
+    /// some margin between them to leave room for this.
+    pub fn dividers(mut self, thickness: f64, color: Color) -> Widget {
+        if let Some(container) = self.widget.downcast_mut::<Container>() {
+            container.divider = Some((thickness, color));
+        } else {
+            panic!("dividers called on something that isn't a row or column");
+        }
+        self
+    }
+
+    /// Only for rows/columns. Makes this a modal dialog: while it's visible, everything else in the
+    /// panel is dimmed and ignores events, mouse clicks outside of it are swallowed, Tab only moves
+    /// focus between its members, and Escape produces `Outcome::Clicked(cancel)`.
+    pub fn modal<I: Into<String>>(mut self, cancel: I) -> Widget {
+        if let Some(container) = self.widget.downcast_mut::<Container>() {
+            container.modal = Some(cancel.into());
+        } else {
+            panic!("modal called on something that isn't a row or column");
+        }
+        self
+    }
+
+    /// Only for rows/columns. Members can be dragged with the mouse into a new order; others shift
+    /// out of the way while dragging. Dropping produces `Outcome::Reordered` with `name` and the
+    /// new order, which the caller should remember for the next time it builds the panel. Members
+    /// need IDs, and since they move around, any margin between them should be the same for every
+    /// member.
+    pub fn reorderable<I: Into<String>>(mut self, name: I) -> Widget {
+        let name = name.into();
+        if let Some(container) = self.widget.downcast_mut::<Container>() {
+            container.reorder = Some(Reorder::new(name.clone()));
+        } else {
+            panic!("reorderable called on something that isn't a row or column");
+        }
+        self.named(name)
+    }
+
+    /// In a reorderable row or column, this member can't be dragged. It keeps its place while the
+    /// others move around it, which suits headers.
+    pub fn pinned(mut self) -> Widget {
+        self.pinned = true;
+        self
+    }
+
     pub fn named<I: Into<String>>(mut self, id: I) -> Widget {
         self.id = Some(id.into());
         self
     }
+
+    /// Describe this widget for `Panel::accessibility_tree`. Without this, widgets report their
+    /// built-in role and are labelled by their ID. Rows and columns only show up as a `Group` when
+    /// described this way; otherwise their members are listed directly.
+    pub fn accessible<I: Into<String>>(mut self, role: AccessRole, label: I) -> Widget {
+        self.access = Some((role, label.into()));
+        self
+    }
+
+    /// Start out hidden. Use `Panel::set_visible` to show it later.
+    pub fn hide(mut self) -> Widget {
+        self.visible = false;
+        self
+    }
+
+    /// When hidden, keep taking up the same space, so nothing else moves around.
+    pub fn reserve_space_when_hidden(mut self) -> Widget {
+        self.reserve_space = true;
+        self
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// The caller must recompute layout afterwards; `Panel::set_visible` does both.
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// After the mouse rests over this widget for a moment, show a tooltip. Only works for
+    /// members of a row or column.
+    pub fn with_tooltip(mut self, txt: Text) -> Widget {
+        self.tooltip = Some(WithTooltip::new(txt));
+        self
+    }
+
+    /// How long the mouse has to rest over this widget before the tooltip from `with_tooltip`
+    /// appears.
+    pub fn tooltip_delay(mut self, delay: Duration) -> Widget {
+        self.tooltip
+            .as_mut()
+            .expect("tooltip_delay needs with_tooltip first")
+            .delay = delay;
+        self
+    }
 }
 
 // Convenient?? constructors
@@ -319,11 +533,18 @@ impl Widget {
                 style: Style {
                     ..Default::default()
                 },
+                pct_of_parent: None,
+                aspect_ratio: None,
             },
             rect: ScreenRectangle::placeholder(),
             bg: None,
             bg_batch: None,
             id: None,
+            visible: true,
+            reserve_space: false,
+            tooltip: None,
+            access: None,
+            pinned: false,
         }
     }
 
@@ -398,6 +619,50 @@ impl Widget {
         Widget::new(Box::new(Container::new(true, new)))
     }
 
+    /// Like a row, but members that would run past `max_width` wrap onto a new line.
+    pub fn wrap(widgets: Vec<Widget>, max_width: f64) -> Widget {
+        let mut new = Vec::new();
+        let len = widgets.len();
+        for (idx, w) in widgets.into_iter().enumerate() {
+            if idx == len - 1 {
+                new.push(w);
+            } else {
+                new.push(w.margin_right(10));
+            }
+        }
+        Widget::new(Box::new(Container::wrap(new, max_width)))
+    }
+
+    /// A row or column, picked by the window width whenever the layout is computed. Of the
+    /// breakpoints whose `min_width` the window satisfies, the biggest applies; if the window is
+    /// narrower than all of them, the smallest does. The breakpoint's spacing replaces any right
+    /// or bottom margin on the members.
+    ///
+    /// Nested responsive containers each compare against the whole window's width, not the space
+    /// their parent leaves them, so inner containers usually want smaller thresholds.
+    pub fn responsive(widgets: Vec<Widget>, breakpoints: Vec<Breakpoint>) -> Widget {
+        Widget::new(Box::new(Container::responsive(widgets, breakpoints)))
+    }
+
+    /// A row when the window is at least `min_width` wide, otherwise a column.
+    pub fn row_or_col(widgets: Vec<Widget>, min_width: f64) -> Widget {
+        Widget::responsive(
+            widgets,
+            vec![
+                Breakpoint {
+                    min_width,
+                    is_row: true,
+                    spacing: 10,
+                },
+                Breakpoint {
+                    min_width: 0.0,
+                    is_row: false,
+                    spacing: 10,
+                },
+            ],
+        )
+    }
+
     pub fn custom_col(widgets: Vec<Widget>) -> Widget {
         Widget::new(Box::new(Container::new(false, widgets)))
     }
@@ -443,16 +708,15 @@ impl Widget {
 
             let mut nodes = vec![];
             self.get_flexbox(root, &mut stretch, &mut nodes);
-            nodes.reverse();
-
-            let container_size = Size {
-                width: Number::Undefined,
-                height: Number::Undefined,
-            };
-            stretch.compute_layout(root, container_size).unwrap();
-
-            self.apply_flexbox(&stretch, &mut nodes, 0.0, 0.0, (0.0, 0.0), ctx, true, true);
-            assert!(nodes.is_empty());
+            self.compute_layout(&mut stretch, root, &nodes);
+
+            let mut layouts = nodes
+                .into_iter()
+                .rev()
+                .map(|n| *stretch.layout(n).unwrap())
+                .collect();
+            self.apply_flexbox(&mut layouts, 0.0, 0.0, (0.0, 0.0), ctx, true, true);
+            assert!(layouts.is_empty());
         }
 
         // Now build one big batch from all of the geometry, which now has the correct top left
@@ -486,6 +750,9 @@ impl Widget {
 // Internals
 impl Widget {
     pub(crate) fn draw(&self, g: &mut GfxCtx) {
+        if !self.visible {
+            return;
+        }
         // Don't draw these yet; clipping is still in effect.
         if self.id == Some("horiz scrollbar".to_string())
             || self.id == Some("vert scrollbar".to_string())
@@ -500,27 +767,99 @@ impl Widget {
         self.widget.draw(g);
     }
 
+    fn flexbox_style(&self) -> Style {
+        let mut style = self.layout.style.clone();
+        if !self.visible && !self.reserve_space {
+            style.display = Display::None;
+        }
+        style
+    }
+
+    /// Arranges responsive containers for the current window width.
+    pub(crate) fn apply_breakpoints(&mut self, window_width: f64) {
+        if let Some(container) = self.widget.downcast_mut::<Container>() {
+            if let Some(bp) = choose_breakpoint(&container.breakpoints, window_width).cloned() {
+                container.is_row = bp.is_row;
+                let len = container.members.len();
+                for (idx, w) in container.members.iter_mut().enumerate() {
+                    let spacing = if idx == len - 1 {
+                        Dimension::Undefined
+                    } else {
+                        Dimension::Points(bp.spacing as f32)
+                    };
+                    let margin = &mut w.layout.style.margin;
+                    if bp.is_row {
+                        margin.end = spacing;
+                        margin.bottom = Dimension::Undefined;
+                    } else {
+                        margin.end = Dimension::Undefined;
+                        margin.bottom = spacing;
+                    }
+                }
+            }
+            for w in &mut container.members {
+                w.apply_breakpoints(window_width);
+            }
+        }
+    }
+
     // Populate a flattened list of Nodes, matching the traversal order
     fn get_flexbox(&self, parent: Node, stretch: &mut Stretch, nodes: &mut Vec<Node>) {
         if let Some(container) = self.widget.downcast_ref::<Container>() {
-            let mut style = self.layout.style.clone();
+            let mut style = self.flexbox_style();
             style.flex_direction = if container.is_row {
                 FlexDirection::Row
             } else {
                 FlexDirection::Column
             };
+            if let Some(width) = container.wrap_width {
+                style.size.width = Dimension::Points(width as f32);
+                style.flex_wrap = FlexWrap::Wrap;
+            }
             let node = stretch.new_node(style, Vec::new()).unwrap();
             nodes.push(node);
+
+            let total_pct: f64 = container
+                .members
+                .iter()
+                .filter_map(|w| w.layout.pct_of_parent)
+                .map(|pct| pct.inner())
+                .sum();
+            let scale = if total_pct > 1.0 {
+                if cfg!(debug_assertions) {
+                    warn!(
+                        "Members of {:?} ask for {}% of it; scaling them down to fit",
+                        self.id,
+                        total_pct * 100.0
+                    );
+                }
+                1.0 / total_pct
+            } else {
+                1.0
+            };
             for widget in &container.members {
+                let first_node = nodes.len();
                 widget.get_flexbox(node, stretch, nodes);
+                if let Some(pct) = widget.layout.pct_of_parent {
+                    let member = nodes[first_node];
+                    let mut style = stretch.style(member).unwrap().clone();
+                    let size = Dimension::Percent((pct.inner() * scale) as f32);
+                    if container.is_row {
+                        style.size.width = size;
+                    } else {
+                        style.size.height = size;
+                    }
+                    style.flex_shrink = 0.0;
+                    stretch.set_style(member, style).unwrap();
+                }
             }
             stretch.add_child(parent, node).unwrap();
             return;
         } else {
-            let mut style = self.layout.style.clone();
+            let mut style = self.flexbox_style();
             style.size = Size {
-                width: Dimension::Points(self.widget.get_dims().width as f32),
-                height: Dimension::Points(self.widget.get_dims().height as f32),
+                width: Dimension::Points(self.widget.intrinsic_dims().width as f32),
+                height: Dimension::Points(self.widget.intrinsic_dims().height as f32),
             };
             let node = stretch.new_node(style, Vec::new()).unwrap();
             stretch.add_child(parent, node).unwrap();
@@ -531,8 +870,7 @@ impl Widget {
     // TODO Clean up argument passing
     fn apply_flexbox(
         &mut self,
-        stretch: &Stretch,
-        nodes: &mut Vec<Node>,
+        layouts: &mut Vec<Layout>,
         dx: f64,
         dy: f64,
         scroll_offset: (f64, f64),
@@ -540,7 +878,7 @@ impl Widget {
         recompute_layout: bool,
         defer_draw: bool,
     ) {
-        let result = stretch.layout(nodes.pop().unwrap()).unwrap();
+        let result = layouts.pop().unwrap();
         let x: f64 = result.location.x.into();
         let y: f64 = result.location.y.into();
         let width: f64 = result.size.width.into();
@@ -585,8 +923,7 @@ impl Widget {
             // layout() doesn't return absolute position; it's relative to the container.
             for widget in &mut container.members {
                 widget.apply_flexbox(
-                    stretch,
-                    nodes,
+                    layouts,
                     x + dx,
                     y + dy,
                     scroll_offset,
@@ -596,7 +933,84 @@ impl Widget {
                 );
             }
         } else {
-            self.widget.set_pos(top_left);
+            self.widget
+                .set_pos(top_left, ScreenDims::new(width, height));
+        }
+    }
+
+    /// Lays out the nodes from `get_flexbox`. Members with an aspect ratio need a second pass, once
+    /// the first has decided how much space they get.
+    pub(crate) fn compute_layout(&self, stretch: &mut Stretch, root: Node, nodes: &[Node]) {
+        // TODO Express more simply. Constraining this seems useless.
+        let unconstrained = || Size {
+            width: Number::Undefined,
+            height: Number::Undefined,
+        };
+        stretch.compute_layout(root, unconstrained()).unwrap();
+        if self.apply_aspect_ratios(stretch, nodes, &mut 0, true) {
+            stretch.compute_layout(root, unconstrained()).unwrap();
+        }
+    }
+
+    // Returns true if any node changed size. Walks the nodes in the same order as get_flexbox.
+    fn apply_aspect_ratios(
+        &self,
+        stretch: &mut Stretch,
+        nodes: &[Node],
+        next: &mut usize,
+        in_row: bool,
+    ) -> bool {
+        let node = nodes[*next];
+        *next += 1;
+
+        let mut changed = false;
+        if let Some(ratio) = self.layout.aspect_ratio {
+            let is_set = |d: &Dimension| matches!(d, Dimension::Points(_) | Dimension::Percent(_));
+            let has_pct = self.layout.pct_of_parent.is_some();
+            let width_set = is_set(&self.layout.style.size.width) || (has_pct && in_row);
+            let height_set = is_set(&self.layout.style.size.height) || (has_pct && !in_row);
+            let from_width = if width_set != height_set {
+                width_set
+            } else {
+                in_row
+            };
+
+            let size = stretch.layout(node).unwrap().size;
+            let mut style = stretch.style(node).unwrap().clone();
+            if from_width {
+                style.size.height = Dimension::Points(size.width / ratio as f32);
+            } else {
+                style.size.width = Dimension::Points(size.height * ratio as f32);
+            }
+            stretch.set_style(node, style).unwrap();
+            changed = true;
+        }
+
+        if let Some(container) = self.widget.downcast_ref::<Container>() {
+            for w in &container.members {
+                changed |= w.apply_aspect_ratios(stretch, nodes, next, container.is_row);
+            }
+        }
+        changed
+    }
+
+    // Everything about this widget and its members that the flexbox layout reads, in the same order
+    // as get_flexbox
+    fn collect_layout_inputs(&self, results: &mut Vec<LayoutInput>) {
+        if let Some(container) = self.widget.downcast_ref::<Container>() {
+            results.push(LayoutInput::Container {
+                is_row: container.is_row,
+                members: container.members.len(),
+                visible: self.visible,
+            });
+            for w in &container.members {
+                w.collect_layout_inputs(results);
+            }
+        } else {
+            results.push(LayoutInput::Leaf {
+                dims: self.widget.intrinsic_dims(),
+                visible: self.visible,
+            });
         }
     }
 
@@ -634,6 +1048,14 @@ impl Widget {
 
     fn restore(&mut self, ctx: &mut EventCtx, prev: &Panel) {
         if let Some(container) = self.widget.downcast_mut::<Container>() {
+            if let Some(ref id) = self.id {
+                if let Some(old) = prev
+                    .maybe_find(id)
+                    .and_then(|w| w.widget.downcast_ref::<Container>())
+                {
+                    container.start_animation(ctx, old);
+                }
+            }
             for w in &mut container.members {
                 w.restore(ctx, prev);
             }
@@ -670,6 +1092,24 @@ impl Widget {
             .unwrap_or(false)
     }
 
+    /// Only describes layout that's already been computed; widgets that haven't been placed by a
+    /// Panel yet will have zeroed positions.
+    pub fn layout_snapshot(&self) -> LayoutSnapshot {
+        LayoutSnapshot {
+            kind: self.widget.layout_kind(),
+            id: self.id.clone(),
+            x: self.rect.x1,
+            y: self.rect.y1,
+            width: self.rect.width(),
+            height: self.rect.height(),
+            members: self
+                .widget
+                .downcast_ref::<Container>()
+                .map(|c| c.members.iter().map(|w| w.layout_snapshot()).collect())
+                .unwrap_or_else(Vec::new),
+        }
+    }
+
     fn find(&self, name: &str) -> Option<&Widget> {
         if self.id == Some(name.to_string()) {
             return Some(self);
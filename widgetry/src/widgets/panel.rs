@@ -2,16 +2,17 @@ use std::collections::HashSet;
 
 use stretch::geometry::Size;
 use stretch::node::Stretch;
-use stretch::number::Number;
+use stretch::result::Layout;
 use stretch::style::{Dimension, Style};
 
 use geom::{Percent, Polygon};
 
-use crate::widgets::Container;
+use crate::widgets::{Container, LayoutInput};
 use crate::{
-    Autocomplete, Checkbox, Color, Dropdown, EventCtx, GfxCtx, HorizontalAlignment, Menu, Outcome,
-    PersistentSplit, ScreenDims, ScreenPt, ScreenRectangle, Slider, Spinner, TextBox,
-    VerticalAlignment, Widget, WidgetImpl, WidgetOutput,
+    AccessNode, Autocomplete, Checkbox, Color, ContainerState, Dropdown, EventCtx, GfxCtx,
+    HorizontalAlignment, LayoutSnapshot, Menu, Outcome, PersistentSplit, ScreenDims, ScreenPt,
+    ScreenRectangle, Slider, Spinner, TextBox, VerticalAlignment, Widget, WidgetImpl, WidgetOutput,
+    WidgetPath,
 };
 
 pub struct Panel {
@@ -19,12 +20,25 @@ pub struct Panel {
     horiz: HorizontalAlignment,
     vert: VerticalAlignment,
     dims: Dims,
+    keyboard_focus: bool,
 
     scrollable_x: bool,
     scrollable_y: bool,
     contents_dims: ScreenDims,
     container_dims: ScreenDims,
     clip_rect: Option<ScreenRectangle>,
+    // Reused while nothing the layout depends on changes
+    layout_cache: Option<LayoutCache>,
+}
+
+struct LayoutCache {
+    window_dims: ScreenDims,
+    container_dims: ScreenDims,
+    scroll_offset: (f64, f64),
+    inputs: Vec<LayoutInput>,
+    top_left: ScreenPt,
+    // In the order apply_flexbox consumes them
+    layouts: Vec<Layout>,
 }
 
 impl Panel {
@@ -34,6 +48,7 @@ impl Panel {
             horiz: HorizontalAlignment::Center,
             vert: VerticalAlignment::Center,
             dims: Dims::MaxPercent(Percent::int(100), Percent::int(100)),
+            keyboard_focus: false,
         }
     }
 
@@ -143,6 +158,7 @@ impl Panel {
     // optimization, we could replace all the current call sites with a "dirty" flag, e.g.
     // `set_needs_layout()` and then call `layout_if_needed()` once at the last possible moment
     fn recompute_layout(&mut self, ctx: &EventCtx, recompute_bg: bool) {
+        self.top_level.apply_breakpoints(ctx.canvas.window_width);
         self.recompute_scrollbar_layout(ctx);
         let mut stretch = Stretch::new();
         let root = stretch
@@ -156,14 +172,7 @@ impl Panel {
 
         let mut nodes = vec![];
         self.top_level.get_flexbox(root, &mut stretch, &mut nodes);
-        nodes.reverse();
-
-        // TODO Express more simply. Constraining this seems useless.
-        let container_size = Size {
-            width: Number::Undefined,
-            height: Number::Undefined,
-        };
-        stretch.compute_layout(root, container_size).unwrap();
+        self.top_level.compute_layout(&mut stretch, root, &nodes);
 
         // TODO I'm so confused why these 2 are acting differently. :(
         let effective_dims = if self.scrollable_x || self.scrollable_y {
@@ -176,9 +185,14 @@ impl Panel {
             .canvas
             .align_window(effective_dims, self.horiz, self.vert);
         let offset = self.scroll_offset();
+        let layouts: Vec<Layout> = nodes
+            .into_iter()
+            .rev()
+            .map(|n| *stretch.layout(n).unwrap())
+            .collect();
+        let mut remaining = layouts.clone();
         self.top_level.apply_flexbox(
-            &stretch,
-            &mut nodes,
+            &mut remaining,
             top_left.x,
             top_left.y,
             offset,
@@ -186,7 +200,53 @@ impl Panel {
             recompute_bg,
             false,
         );
-        assert!(nodes.is_empty());
+        assert!(remaining.is_empty());
+
+        let mut inputs = Vec::new();
+        self.top_level.collect_layout_inputs(&mut inputs);
+        self.layout_cache = Some(LayoutCache {
+            window_dims: ctx.canvas.get_window_dims(),
+            container_dims: self.container_dims,
+            scroll_offset: offset,
+            inputs,
+            top_left,
+            layouts,
+        });
+    }
+
+    /// Like `recompute_layout`, but if no member changed size or visibility and the available
+    /// space and scroll offset are the same, skips solving the flexbox again and reuses the last
+    /// positions. Widgets are still told where they are, since they may have replaced parts of
+    /// themselves.
+    fn recompute_layout_if_needed(&mut self, ctx: &EventCtx) {
+        let mut inputs = Vec::new();
+        self.top_level.collect_layout_inputs(&mut inputs);
+        let offset = self.scroll_offset();
+        let cached = match self.layout_cache {
+            Some(ref cache)
+                if cache.window_dims == ctx.canvas.get_window_dims()
+                    && cache.container_dims == self.container_dims
+                    && cache.scroll_offset == offset
+                    && cache.inputs == inputs =>
+            {
+                Some((cache.top_left, cache.layouts.clone()))
+            }
+            _ => None,
+        };
+        if let Some((top_left, mut layouts)) = cached {
+            // The sizes are the same, so backgrounds don't need to be redrawn
+            self.top_level.apply_flexbox(
+                &mut layouts,
+                top_left.x,
+                top_left.y,
+                offset,
+                ctx,
+                false,
+                false,
+            );
+        } else {
+            self.recompute_layout(ctx, true);
+        }
     }
 
     fn scroll_offset(&self) -> (f64, f64) {
@@ -267,8 +327,13 @@ impl Panel {
         let before = self.scroll_offset();
         let mut output = WidgetOutput::new();
         self.top_level.widget.event(ctx, &mut output);
+        if self.keyboard_focus {
+            if let Some(container) = self.top_level.widget.downcast_mut::<Container>() {
+                container.focus_root().focus_event(ctx);
+            }
+        }
         if self.scroll_offset() != before || output.redo_layout {
-            self.recompute_layout(ctx, true);
+            self.recompute_layout_if_needed(ctx);
         }
 
         output.outcome
@@ -328,6 +393,30 @@ impl Panel {
         ctx.no_op_event(true, |ctx| assert_eq!(self.event(ctx), Outcome::Nothing));
     }
 
+    /// Captures the scroll position, toggles, and keyboard focus, to carry over to a rebuilt
+    /// version of this panel. See `ContainerState`.
+    pub fn container_state(&self) -> ContainerState {
+        let mut state = ContainerState {
+            scroll_offset: self.scroll_offset(),
+            ..Default::default()
+        };
+        if let Some(container) = self.top_level.widget.downcast_ref::<Container>() {
+            container.save_state(&mut state);
+        }
+        state
+    }
+
+    pub fn restore_container_state(&mut self, ctx: &mut EventCtx, state: &ContainerState) {
+        if let Some(container) = self.top_level.widget.downcast_mut::<Container>() {
+            container.load_state(state);
+        }
+        // Flipping checkboxes can change sizes
+        self.recompute_layout(ctx, false);
+        self.set_scroll_offset(ctx, state.scroll_offset);
+
+        ctx.no_op_event(true, |ctx| assert_eq!(self.event(ctx), Outcome::Nothing));
+    }
+
     pub fn scroll_to_member(&mut self, ctx: &EventCtx, name: String) {
         if let Some(w) = self.top_level.find(&name) {
             let y1 = w.rect.y1;
@@ -337,6 +426,28 @@ impl Panel {
         }
     }
 
+    /// Which widget is under a point. See `Container::member_at`.
+    pub fn member_at(&self, pt: ScreenPt) -> Option<WidgetPath> {
+        self.top_level
+            .widget
+            .downcast_ref::<Container>()?
+            .member_at(pt)
+    }
+
+    /// Describes everything visible in this panel for assistive tools. See `AccessNode`.
+    pub fn accessibility_tree(&self) -> Vec<AccessNode> {
+        self.top_level
+            .widget
+            .downcast_ref::<Container>()
+            .map(|c| c.accessibility_tree())
+            .unwrap_or_else(Vec::new)
+    }
+
+    /// Describes where everything in this panel currently is.
+    pub fn layout_snapshot(&self) -> LayoutSnapshot {
+        self.top_level.layout_snapshot()
+    }
+
     pub fn has_widget(&self, name: &str) -> bool {
         self.top_level.find(name).is_some()
     }
@@ -467,13 +578,35 @@ impl Panel {
             .expect(&format!("Panel doesn't have {}", id));
         new.layout.style = old.layout.style;
         *old = new;
+        self.forget_focus();
         self.recompute_layout(ctx, true);
         // TODO Same no_op_event as align_above? Should we always do this in recompute_layout?
     }
 
+    /// Shows or hides a widget in place, without rebuilding the panel.
+    pub fn set_visible(&mut self, ctx: &mut EventCtx, id: &str, visible: bool) {
+        let widget = self
+            .top_level
+            .find_mut(id)
+            .expect(&format!("Panel doesn't have {}", id));
+        if widget.is_visible() != visible {
+            widget.set_visible(visible);
+            self.forget_focus();
+            self.recompute_layout(ctx, true);
+        }
+    }
+
     /// Removes a widget from the panel. Does not recalculate layout!
     pub fn take(&mut self, id: &str) -> Widget {
-        self.top_level.take(id).unwrap()
+        let widget = self.top_level.take(id).unwrap();
+        self.forget_focus();
+        widget
+    }
+
+    fn forget_focus(&mut self) {
+        if let Some(container) = self.top_level.widget.downcast_mut::<Container>() {
+            container.forget_focus();
+        }
     }
 
     pub fn clicked_outside(&self, ctx: &mut EventCtx) -> bool {
@@ -491,6 +624,7 @@ pub struct PanelBuilder {
     horiz: HorizontalAlignment,
     vert: VerticalAlignment,
     dims: Dims,
+    keyboard_focus: bool,
 }
 
 enum Dims {
@@ -511,12 +645,14 @@ impl PanelBuilder {
             horiz: self.horiz,
             vert: self.vert,
             dims: self.dims,
+            keyboard_focus: self.keyboard_focus,
 
             scrollable_x: false,
             scrollable_y: false,
             contents_dims: ScreenDims::new(0.0, 0.0),
             container_dims: ScreenDims::new(0.0, 0.0),
             clip_rect: None,
+            layout_cache: None,
         };
         if let Dims::ExactPercent(w, h) = panel.dims {
             // Don't set size, because then scrolling breaks -- the actual size has to be based on
@@ -565,6 +701,13 @@ impl PanelBuilder {
         self
     }
 
+    /// Lets Tab and Shift+Tab move focus between the buttons, dropdowns, and text boxes inside.
+    /// The panel then takes every Tab press, so other panels handling the same event won't see it.
+    pub fn keyboard_focus(mut self) -> PanelBuilder {
+        self.keyboard_focus = true;
+        self
+    }
+
     pub fn exact_size_percent(mut self, pct_width: usize, pct_height: usize) -> PanelBuilder {
         self.dims = Dims::ExactPercent((pct_width as f64) / 100.0, (pct_height as f64) / 100.0);
         self
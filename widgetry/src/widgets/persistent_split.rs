@@ -32,7 +32,7 @@ impl<T: 'static + PartialEq + Clone + std::fmt::Debug> PersistentSplit<T> {
                 ctx,
                 GeomBatch::from(vec![(
                     Color::WHITE.alpha(0.5),
-                    Polygon::rectangle(3.0, btn.get_dims().height),
+                    Polygon::rectangle(3.0, btn.intrinsic_dims().height),
                 )]),
             )
             .take_just_draw(),
@@ -50,24 +50,30 @@ impl<T: 'static + PartialEq + Clone> PersistentSplit<T> {
 }
 
 impl<T: 'static + Clone + PartialEq> WidgetImpl for PersistentSplit<T> {
-    fn get_dims(&self) -> ScreenDims {
-        let dims1 = self.btn.get_dims();
-        let dims2 = self.spacer.get_dims();
-        let dims3 = self.dropdown.get_dims();
+    fn intrinsic_dims(&self) -> ScreenDims {
+        let dims1 = self.btn.intrinsic_dims();
+        let dims2 = self.spacer.intrinsic_dims();
+        let dims3 = self.dropdown.intrinsic_dims();
         ScreenDims::new(
             dims1.width + dims2.width + dims3.width,
             dims1.height.max(dims2.height).max(dims3.height),
         )
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
-        self.btn.set_pos(top_left);
-        self.spacer
-            .set_pos(ScreenPt::new(top_left.x + self.btn.dims.width, top_left.y));
-        self.dropdown.set_pos(ScreenPt::new(
-            top_left.x + self.btn.dims.width + self.spacer.get_dims().width,
-            top_left.y,
-        ));
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
+        self.btn.set_pos(top_left, self.btn.dims);
+        let spacer_dims = self.spacer.intrinsic_dims();
+        self.spacer.set_pos(
+            ScreenPt::new(top_left.x + self.btn.dims.width, top_left.y),
+            spacer_dims,
+        );
+        self.dropdown.set_pos(
+            ScreenPt::new(
+                top_left.x + self.btn.dims.width + spacer_dims.width,
+                top_left.y,
+            ),
+            self.dropdown.intrinsic_dims(),
+        );
     }
 
     fn event(&mut self, ctx: &mut EventCtx, output: &mut WidgetOutput) {
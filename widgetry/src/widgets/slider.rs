@@ -1,8 +1,8 @@
 use geom::{Circle, Distance, Polygon, Pt2D};
 
 use crate::{
-    Color, Drawable, EventCtx, GeomBatch, GfxCtx, ScreenDims, ScreenPt, ScreenRectangle, Widget,
-    WidgetImpl, WidgetOutput,
+    AccessRole, Color, Drawable, EventCtx, GeomBatch, GfxCtx, ScreenDims, ScreenPt,
+    ScreenRectangle, Widget, WidgetImpl, WidgetOutput,
 };
 
 pub struct Slider {
@@ -184,7 +184,7 @@ impl Slider {
         (self.current_percent * (num_items as f64 - 1.0)) as usize
     }
 
-    pub(crate) fn set_percent(&mut self, ctx: &EventCtx, percent: f64) {
+    pub fn set_percent(&mut self, ctx: &EventCtx, percent: f64) {
         assert!(percent >= 0.0 && percent <= 1.0);
         self.current_percent = percent;
         self.recalc(ctx);
@@ -253,11 +253,11 @@ impl Slider {
 }
 
 impl WidgetImpl for Slider {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
@@ -274,4 +274,8 @@ impl WidgetImpl for Slider {
         g.canvas
             .mark_covered_area(ScreenRectangle::top_left(self.top_left, self.dims));
     }
+
+    fn access_role(&self) -> Option<AccessRole> {
+        Some(AccessRole::Slider)
+    }
 }
@@ -33,8 +33,8 @@ impl Spinner {
             .take_btn();
 
         let dims = ScreenDims::new(
-            TEXT_WIDTH + up.get_dims().width,
-            up.get_dims().height + down.get_dims().height,
+            TEXT_WIDTH + up.intrinsic_dims().width,
+            up.intrinsic_dims().height + down.intrinsic_dims().height,
         );
         if current < low {
             current = low;
@@ -64,20 +64,22 @@ impl Spinner {
 }
 
 impl WidgetImpl for Spinner {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         // TODO This works, but it'd be kind of cool if we could construct a tiny little Panel
         // here and use that. Wait, why can't we? ...
         self.top_left = top_left;
-        self.up
-            .set_pos(ScreenPt::new(top_left.x + TEXT_WIDTH, top_left.y));
-        self.down.set_pos(ScreenPt::new(
-            top_left.x + TEXT_WIDTH,
-            top_left.y + self.up.get_dims().height,
-        ));
+        self.up.set_pos(
+            ScreenPt::new(top_left.x + TEXT_WIDTH, top_left.y),
+            self.up.dims,
+        );
+        self.down.set_pos(
+            ScreenPt::new(top_left.x + TEXT_WIDTH, top_left.y + self.up.dims.height),
+            self.down.dims,
+        );
     }
 
     fn event(&mut self, ctx: &mut EventCtx, output: &mut WidgetOutput) {
@@ -1,8 +1,8 @@
 use geom::Polygon;
 
 use crate::{
-    text, EventCtx, GeomBatch, GfxCtx, Key, Line, Outcome, ScreenDims, ScreenPt, ScreenRectangle,
-    Text, WidgetImpl, WidgetOutput,
+    text, AccessRole, EventCtx, GeomBatch, GfxCtx, Key, Line, Outcome, ScreenDims, ScreenPt,
+    ScreenRectangle, Text, WidgetImpl, WidgetOutput,
 };
 
 // TODO right now, only a single line
@@ -56,11 +56,11 @@ impl TextBox {
 }
 
 impl WidgetImpl for TextBox {
-    fn get_dims(&self) -> ScreenDims {
+    fn intrinsic_dims(&self) -> ScreenDims {
         self.dims
     }
 
-    fn set_pos(&mut self, top_left: ScreenPt) {
+    fn set_pos(&mut self, top_left: ScreenPt, _: ScreenDims) {
         self.top_left = top_left;
     }
 
@@ -113,6 +113,14 @@ impl WidgetImpl for TextBox {
         }
     }
 
+    fn can_focus(&self) -> bool {
+        true
+    }
+
+    fn set_focus(&mut self, focused: bool) {
+        self.has_focus = focused;
+    }
+
     fn draw(&self, g: &mut GfxCtx) {
         // TODO Cache
         let mut batch = GeomBatch::from(vec![(
@@ -123,4 +131,8 @@ impl WidgetImpl for TextBox {
         let draw = g.upload(batch);
         g.redraw_at(self.top_left, &draw);
     }
+
+    fn access_role(&self) -> Option<AccessRole> {
+        Some(AccessRole::TextBox)
+    }
 }